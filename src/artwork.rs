@@ -0,0 +1,244 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::simulation::*;
+
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+pub struct ArtworkPlugin;
+
+/*
+ * assets/sprites/<species>.png があればそれを描画に使う。
+ * 1 コマ SPRITE_SIZE px 四方で、横に SPRITE_FRAMES コマ、
+ * 行は上から 歩く / 食べる / 死ぬ の順に並べる。
+ * 画像が無ければ今まで通り bevy_prototype_lyon の図形で描く。
+ */
+impl Plugin for ArtworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(load_artwork)
+            .add_system(apply_artwork)
+            .add_system(animate)
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Death).with_system(leave_corpse),
+            )
+            .add_system(animate_corpse);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Walk,
+    Eat,
+    Die,
+}
+
+impl Motion {
+    fn row(self) -> usize {
+        match self {
+            Motion::Walk => 0,
+            Motion::Eat => 1,
+            Motion::Die => 2,
+        }
+    }
+}
+
+pub struct Artwork {
+    images: HashMap<Species, Handle<Image>>,
+    atlases: HashMap<Species, Handle<TextureAtlas>>,
+}
+
+/// 画像が読めなかったので図形のまま描く
+#[derive(Component)]
+pub struct ShapeFallback;
+
+#[derive(Component)]
+pub struct Animation {
+    motion: Motion,
+    frame: usize,
+    timer: Timer,
+    facing_left: bool,
+    last_position: Position,
+    last_satiety: f32,
+}
+
+#[derive(Component)]
+pub struct Corpse {
+    frame: usize,
+    timer: Timer,
+}
+
+fn load_artwork(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let images = Species::ALL
+        .iter()
+        .map(|species| {
+            let path = format!("sprites/{}.png", species.name());
+            (*species, asset_server.load(path.as_str()))
+        })
+        .collect();
+    commands.insert_resource(Artwork {
+        images,
+        atlases: HashMap::new(),
+    });
+}
+
+fn frame_timer() -> Timer {
+    Timer::from_seconds(SPRITE_FRAME_SECS, true)
+}
+
+fn apply_artwork(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut artwork: ResMut<Artwork>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut creature_query: Query<
+        (Entity, &Species, &Position, &Satiety, &mut Visibility),
+        (Without<Animation>, Without<ShapeFallback>),
+    >,
+) {
+    creature_query
+        .iter_mut()
+        .for_each(|(creature, species, pos, satiety, mut visibility)| {
+            let image = match artwork.images.get(species) {
+                Some(image) => image.clone(),
+                None => return,
+            };
+            match asset_server.get_load_state(&image) {
+                LoadState::Loaded => {
+                    let atlas = artwork
+                        .atlases
+                        .entry(*species)
+                        .or_insert_with(|| {
+                            texture_atlases.add(TextureAtlas::from_grid(
+                                image,
+                                Vec2::new(SPRITE_SIZE, SPRITE_SIZE),
+                                SPRITE_FRAMES,
+                                SPRITE_ROWS,
+                            ))
+                        })
+                        .clone();
+
+                    visibility.is_visible = false;
+                    commands
+                        .entity(creature)
+                        .insert(Animation {
+                            motion: Motion::Walk,
+                            frame: 0,
+                            timer: frame_timer(),
+                            facing_left: false,
+                            last_position: *pos,
+                            last_satiety: satiety.val,
+                        })
+                        .with_children(|parent| {
+                            parent.spawn_bundle(species_sprite(atlas, Transform::default()));
+                        });
+                }
+                LoadState::Failed => {
+                    commands.entity(creature).insert(ShapeFallback);
+                }
+                _ => {}
+            }
+        });
+}
+
+fn species_sprite(atlas: Handle<TextureAtlas>, transform: Transform) -> SpriteSheetBundle {
+    SpriteSheetBundle {
+        texture_atlas: atlas,
+        sprite: TextureAtlasSprite {
+            custom_size: Some(Vec2::new(UNIT_WIDTH as f32, UNIT_HEIGHT as f32)),
+            ..Default::default()
+        },
+        transform: Transform {
+            translation: transform.translation + Vec3::new(0.0, 0.0, SPRITE_LAYER_OFFSET),
+            ..transform
+        },
+        ..Default::default()
+    }
+}
+
+fn animate(
+    time: Res<Time>,
+    mut creature_query: Query<(&mut Animation, &Position, &Satiety, &Children)>,
+    mut sprite_query: Query<&mut TextureAtlasSprite>,
+) {
+    creature_query
+        .iter_mut()
+        .for_each(|(mut animation, pos, satiety, children)| {
+            if satiety.val > animation.last_satiety {
+                animation.motion = Motion::Eat;
+                animation.frame = 0;
+            } else if *pos != animation.last_position {
+                if pos.x != animation.last_position.x {
+                    animation.facing_left = pos.x < animation.last_position.x;
+                }
+                if animation.motion != Motion::Eat {
+                    animation.motion = Motion::Walk;
+                }
+            }
+            animation.last_position = *pos;
+            animation.last_satiety = satiety.val;
+
+            animation.timer.tick(time.delta());
+            if animation.timer.just_finished() {
+                animation.frame += 1;
+                // 食べ終わったら歩きに戻る
+                if animation.motion == Motion::Eat && animation.frame >= SPRITE_FRAMES {
+                    animation.motion = Motion::Walk;
+                }
+                animation.frame %= SPRITE_FRAMES;
+            }
+
+            for child in children.iter() {
+                if let Ok(mut sprite) = sprite_query.get_mut(*child) {
+                    sprite.index = animation.motion.row() * SPRITE_FRAMES + animation.frame;
+                    sprite.flip_x = animation.facing_left;
+                }
+            }
+        });
+}
+
+// 死んだ生き物の跡に、死ぬアニメーションだけを残す
+fn leave_corpse(
+    mut commands: Commands,
+    artwork: Res<Artwork>,
+    creature_query: Query<(&Species, &HP, &Transform, &Animation)>,
+) {
+    creature_query
+        .iter()
+        .for_each(|(species, hp, transform, animation)| {
+            if hp.val > 0.0 {
+                return;
+            }
+            if let Some(atlas) = artwork.atlases.get(species) {
+                let mut bundle = species_sprite(atlas.clone(), *transform);
+                bundle.sprite.index = Motion::Die.row() * SPRITE_FRAMES;
+                bundle.sprite.flip_x = animation.facing_left;
+                commands.spawn_bundle(bundle).insert(Corpse {
+                    frame: 0,
+                    timer: frame_timer(),
+                });
+            }
+        });
+}
+
+fn animate_corpse(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut corpse_query: Query<(Entity, &mut Corpse, &mut TextureAtlasSprite)>,
+) {
+    corpse_query
+        .iter_mut()
+        .for_each(|(entity, mut corpse, mut sprite)| {
+            corpse.timer.tick(time.delta());
+            if !corpse.timer.just_finished() {
+                return;
+            }
+            corpse.frame += 1;
+            if corpse.frame >= SPRITE_FRAMES {
+                commands.entity(entity).despawn();
+            } else {
+                sprite.index = Motion::Die.row() * SPRITE_FRAMES + corpse.frame;
+            }
+        });
+}
@@ -0,0 +1,80 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::eat::{nutrition, Meals};
+use crate::input::*;
+use crate::score::Record;
+use crate::simulation::*;
+use crate::title::Screen;
+
+use bevy::prelude::*;
+
+pub struct BagPlugin;
+
+impl Plugin for BagPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(use_bag).add_system_set_to_stage(
+            SimulationStage,
+            tick_set(Tick::Eat).with_system(eat_from_bag),
+        );
+    }
+}
+
+/// Player が持ち歩く食べ物。carrying の間は踏んだ獲物を食べずに BAG_SIZE まで袋に入れる
+#[derive(Component, Default)]
+pub struct Bag {
+    pub items: Vec<Species>,
+    pub carrying: bool,
+    // 次の tick で袋の中身を 1 つ食べる
+    eat_requested: bool,
+}
+
+impl Bag {
+    /// 袋に入れられたら true。そのときは食べない
+    pub fn stash(&mut self, species: Species) -> bool {
+        if !self.carrying || self.items.len() >= BAG_SIZE {
+            return false;
+        }
+        self.items.push(species);
+        true
+    }
+}
+
+// Carry: 袋に入れるかどうかを切り替える, EatCarried: 先に入れたものから食べる
+fn use_bag(screen: Res<Screen>, actions: Res<Actions>, mut bag_query: Query<(&Player, &mut Bag)>) {
+    if *screen != Screen::Playing {
+        return;
+    }
+    bag_query.iter_mut().for_each(|(player, mut bag)| {
+        if actions.just_pressed(Action::Carry.for_player(player.id)) {
+            bag.carrying = !bag.carrying;
+        }
+        if actions.just_pressed(Action::EatCarried.for_player(player.id)) {
+            bag.eat_requested = true;
+        }
+    });
+}
+
+fn eat_from_bag(
+    mut player_query: Query<(Entity, &mut Bag, &mut HP, &mut Satiety), With<Player>>,
+    mut record: ResMut<Record>,
+    mut meals: ResMut<Meals>,
+) {
+    player_query
+        .iter_mut()
+        .for_each(|(player, mut bag, mut hp, mut satiety)| {
+            if !bag.eat_requested {
+                return;
+            }
+            bag.eat_requested = false;
+            if hp.val <= 0.0 || bag.items.is_empty() {
+                return;
+            }
+            let species = bag.items.remove(0);
+            let (healing_hp, healing_satiety) = nutrition(species);
+            let before = hp.val;
+            hp.val = f32::min(hp.val + healing_hp, hp.max);
+            satiety.val += healing_satiety;
+            record.add(species);
+            meals.add(player, hp.val - before, healing_satiety);
+        });
+}
@@ -0,0 +1,421 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::get_render_position;
+use crate::input::*;
+
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::prelude::*;
+
+pub struct CameraPlugin;
+
+/*
+ * カメラ
+ * 遊んでいる間は Player を追いかける
+ * マウスホイール / ZoomIn ZoomOut: 拡大縮小  Pan / 中ボタンドラッグ: 移動 (追いかけるのをやめる)
+ * Follow: また Player を追いかける
+ * 島の外へは出ない。右下に島全体のミニマップを出す
+ */
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MapBounds::screen())
+            .add_system(camera_zoom)
+            .add_system(camera_pan.label("camera_pan"))
+            .add_system(camera_follow.label("camera_follow").after("camera_pan"))
+            .add_system(camera_clamp.after("camera_follow"))
+            .add_system(minimap_terrain.label("minimap_terrain"))
+            .add_system(minimap_dots.after("minimap_terrain"))
+            .add_system(minimap_view);
+    }
+}
+
+#[derive(Component)]
+pub struct MainCamera {
+    pub follow: bool,
+}
+
+/// カメラが動ける範囲 (描画座標)
+pub struct MapBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+    // ミニマップの原点にするマス
+    pub origin: Position,
+}
+
+impl MapBounds {
+    // 最初の画面。島が小さくても今まで通りの位置に描く
+    fn screen() -> MapBounds {
+        let half = Vec2::new(
+            (SCREEN_WIDTH * UNIT_WIDTH) as f32,
+            (SCREEN_HEIGHT * UNIT_HEIGHT) as f32,
+        ) / 2.0;
+        MapBounds {
+            min: -half,
+            max: half,
+            origin: Position { x: 0, y: 0, z: 0 },
+        }
+    }
+}
+
+pub type CameraQuery<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static OrthographicProjection), With<MainCamera>>;
+
+/// ゲームの画面と UI のカメラを置く
+pub fn spawn_cameras(commands: &mut Commands) {
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera { follow: true });
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+// 描画座標からマスに直す (get_render_position の逆)
+fn world_to_grid(world: Vec2) -> Position {
+    let origin = get_render_position(&Position { x: 0, y: 0, z: 0 });
+    Position {
+        x: ((world.x - origin.x as f32) / UNIT_WIDTH as f32 + 0.5).floor() as i32,
+        y: ((world.y - origin.y as f32) / UNIT_HEIGHT as f32 + 0.5).floor() as i32,
+        z: 0,
+    }
+}
+
+/// ウィンドウ上の位置 (左下が原点) をマスに直す
+pub fn window_grid(
+    windows: &Windows,
+    camera_query: &CameraQuery,
+    window_pos: Vec2,
+) -> Option<Position> {
+    let window = windows.get_primary()?;
+    let (transform, projection) = camera_query.get_single().ok()?;
+    let size = Vec2::new(window.width(), window.height());
+    let world = transform.translation.truncate() + (window_pos - size / 2.0) * projection.scale;
+    Some(world_to_grid(world))
+}
+
+/// カーソルのあるマス
+pub fn cursor_grid(windows: &Windows, camera_query: &CameraQuery) -> Option<Position> {
+    let cursor = windows.get_primary()?.cursor_position()?;
+    window_grid(windows, camera_query, cursor)
+}
+
+fn camera_zoom(
+    actions: Res<Actions>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    let mut zoom = 1.0;
+    wheel_events.iter().for_each(|wheel| {
+        zoom *= CAMERA_ZOOM_STEP.powf(-wheel.y.signum());
+    });
+    if actions.just_pressed(Action::ZoomIn) {
+        zoom /= CAMERA_ZOOM_STEP;
+    }
+    if actions.just_pressed(Action::ZoomOut) {
+        zoom *= CAMERA_ZOOM_STEP;
+    }
+    if zoom == 1.0 {
+        return;
+    }
+    camera_query.iter_mut().for_each(|mut projection| {
+        projection.scale = (projection.scale * zoom).clamp(CAMERA_MIN_ZOOM, CAMERA_MAX_ZOOM);
+    });
+}
+
+fn camera_pan(
+    time: Res<Time>,
+    actions: Res<Actions>,
+    mouse: Res<Input<MouseButton>>,
+    mut motion_events: EventReader<MouseMotion>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection, &mut MainCamera)>,
+) {
+    let mut direction = actions.pan_axis;
+    [
+        (Action::PanLeft, Vec2::new(-1.0, 0.0)),
+        (Action::PanRight, Vec2::new(1.0, 0.0)),
+        (Action::PanUp, Vec2::new(0.0, 1.0)),
+        (Action::PanDown, Vec2::new(0.0, -1.0)),
+    ]
+    .iter()
+    .for_each(|(action, dir)| {
+        if actions.pressed(*action) {
+            direction += *dir;
+        }
+    });
+    let mut delta = if direction.length() >= GAMEPAD_DEADZONE {
+        direction.clamp_length_max(1.0) * CAMERA_PAN_SPEED * time.delta_seconds()
+    } else {
+        Vec2::ZERO
+    };
+    let dragging = mouse.pressed(MouseButton::Middle);
+    motion_events.iter().for_each(|motion| {
+        if dragging {
+            // 画面の y は下向き
+            delta += Vec2::new(-motion.delta.x, motion.delta.y);
+        }
+    });
+
+    camera_query
+        .iter_mut()
+        .for_each(|(mut transform, projection, mut camera)| {
+            if actions.just_pressed(Action::Follow) {
+                camera.follow = true;
+            }
+            if delta != Vec2::ZERO {
+                camera.follow = false;
+                transform.translation += (delta * projection.scale).extend(0.0);
+            }
+        });
+}
+
+fn camera_follow(
+    player_query: Query<&Transform, (With<Player>, Without<MainCamera>)>,
+    mut camera_query: Query<(&mut Transform, &MainCamera)>,
+) {
+    // 2 人のときは間を映す
+    let (sum, count) = player_query
+        .iter()
+        .fold((Vec3::ZERO, 0), |(sum, count), player| {
+            (sum + player.translation, count + 1)
+        });
+    if count == 0 {
+        return;
+    }
+    let target = sum / count as f32;
+    camera_query.iter_mut().for_each(|(mut transform, camera)| {
+        if camera.follow {
+            transform.translation.x = target.x;
+            transform.translation.y = target.y;
+        }
+    });
+}
+
+// 見えている範囲が島の外に出ないようにする
+fn camera_clamp(
+    windows: Res<Windows>,
+    bounds: Res<MapBounds>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    camera_query
+        .iter_mut()
+        .for_each(|(mut transform, projection)| {
+            let half = Vec2::new(window.width(), window.height()) / 2.0 * projection.scale;
+            let clamp = |value: f32, min: f32, max: f32, half: f32| {
+                if max - min <= half * 2.0 {
+                    (min + max) / 2.0
+                } else {
+                    value.clamp(min + half, max - half)
+                }
+            };
+            transform.translation.x =
+                clamp(transform.translation.x, bounds.min.x, bounds.max.x, half.x);
+            transform.translation.y =
+                clamp(transform.translation.y, bounds.min.y, bounds.max.y, half.y);
+        });
+}
+
+#[derive(Component)]
+pub struct Minimap;
+
+#[derive(Component)]
+pub struct MinimapTile;
+
+/// ミニマップ上で target の位置を示す点
+#[derive(Component)]
+pub struct MinimapDot {
+    target: Entity,
+}
+
+#[derive(Component)]
+pub struct MinimapView;
+
+pub fn spawn_minimap(commands: &mut Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                overflow: Overflow::Hidden,
+                position: Rect {
+                    right: Val::Px(UNIT_WIDTH as f32),
+                    bottom: Val::Px(UNIT_HEIGHT as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            color: UiColor(Color::rgba(0.0, 0.0, 0.3, 0.6)),
+            ..Default::default()
+        })
+        .insert(Minimap)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    color: UiColor(Color::rgba(1.0, 1.0, 1.0, 0.3)),
+                    ..Default::default()
+                })
+                .insert(MinimapView);
+        });
+}
+
+fn minimap_tile_size(bounds: &MapBounds) -> f32 {
+    let size = bounds.max - bounds.min;
+    let tiles = f32::max(size.x / UNIT_WIDTH as f32, size.y / UNIT_HEIGHT as f32);
+    f32::min(MINIMAP_TILE, MINIMAP_MAX_SIZE / tiles)
+}
+
+// ミニマップ上の位置 (左下から)
+fn minimap_offset(bounds: &MapBounds, pos: &Position) -> Vec2 {
+    let tile = minimap_tile_size(bounds);
+    Vec2::new(
+        (pos.x - bounds.origin.x) as f32 * tile,
+        (pos.y - bounds.origin.y) as f32 * tile,
+    )
+}
+
+fn minimap_node(offset: Vec2, size: Vec2, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(offset.x),
+                bottom: Val::Px(offset.y),
+                ..default()
+            },
+            ..default()
+        },
+        color: UiColor(color),
+        ..Default::default()
+    }
+}
+
+// 島ができたら、動ける範囲とミニマップの地形を作り直す
+fn minimap_terrain(
+    mut commands: Commands,
+    mut bounds: ResMut<MapBounds>,
+    added_query: Query<&Field, Added<Field>>,
+    field_query: Query<&Position, With<Field>>,
+    mut minimap_query: Query<(Entity, &mut Style), With<Minimap>>,
+    tile_query: Query<Entity, With<MinimapTile>>,
+) {
+    if added_query.is_empty() {
+        return;
+    }
+    // 船が回る外周の分、1 マス広くとる
+    let (mut min, mut max) = (IVec2::splat(i32::MAX), IVec2::splat(i32::MIN));
+    field_query.iter().for_each(|pos| {
+        min = min.min(IVec2::new(pos.x - 1, pos.y - 1));
+        max = max.max(IVec2::new(pos.x + 1, pos.y + 1));
+    });
+    let unit = Vec2::new(UNIT_WIDTH as f32, UNIT_HEIGHT as f32);
+    let render = |x: i32, y: i32| {
+        let pos = get_render_position(&Position { x, y, z: 0 });
+        Vec2::new(pos.x as f32, pos.y as f32)
+    };
+    let screen = MapBounds::screen();
+    *bounds = MapBounds {
+        min: (render(min.x, min.y) - unit / 2.0).min(screen.min),
+        max: (render(max.x, max.y) + unit / 2.0).max(screen.max),
+        origin: Position {
+            x: min.x,
+            y: min.y,
+            z: 0,
+        },
+    };
+
+    let (minimap, mut style) = match minimap_query.get_single_mut() {
+        Ok(minimap) => minimap,
+        Err(_) => return,
+    };
+    let tile = minimap_tile_size(&bounds);
+    let size = (max - min + IVec2::ONE).as_vec2() * tile;
+    style.size = Size::new(Val::Px(size.x), Val::Px(size.y));
+
+    tile_query
+        .iter()
+        .for_each(|entity| commands.entity(entity).despawn());
+    commands.entity(minimap).with_children(|parent| {
+        field_query.iter().for_each(|pos| {
+            parent
+                .spawn_bundle(minimap_node(
+                    minimap_offset(&bounds, pos),
+                    Vec2::splat(tile),
+                    Color::GREEN,
+                ))
+                .insert(MinimapTile);
+        });
+    });
+}
+
+fn minimap_dots(
+    mut commands: Commands,
+    bounds: Res<MapBounds>,
+    added_query: Query<(Entity, Option<&Player>), Or<(Added<Player>, Added<Ship>)>>,
+    target_query: Query<&Position>,
+    minimap_query: Query<Entity, With<Minimap>>,
+    mut dot_query: Query<(Entity, &MinimapDot, &mut Style)>,
+) {
+    let minimap = match minimap_query.get_single() {
+        Ok(minimap) => minimap,
+        Err(_) => return,
+    };
+    let tile = minimap_tile_size(&bounds);
+    added_query.iter().for_each(|(target, player)| {
+        let color = match player {
+            Some(player) => PLAYER_COLORS[player.id],
+            None => Color::WHITE,
+        };
+        commands.entity(minimap).with_children(|parent| {
+            parent
+                .spawn_bundle(minimap_node(Vec2::ZERO, Vec2::splat(tile), color))
+                .insert(MinimapDot { target });
+        });
+    });
+
+    dot_query.iter_mut().for_each(|(entity, dot, mut style)| {
+        match target_query.get(dot.target) {
+            Ok(pos) => {
+                let offset = minimap_offset(&bounds, pos);
+                style.position.left = Val::Px(offset.x);
+                style.position.bottom = Val::Px(offset.y);
+            }
+            // 食べられた、船に乗った
+            Err(_) => commands.entity(entity).despawn(),
+        }
+    });
+}
+
+fn minimap_view(
+    windows: Res<Windows>,
+    bounds: Res<MapBounds>,
+    camera_query: CameraQuery,
+    mut view_query: Query<&mut Style, With<MinimapView>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let (transform, projection) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let half = Vec2::new(window.width(), window.height()) / 2.0 * projection.scale;
+    let center = transform.translation.truncate();
+    let bottom_left = world_to_grid(center - half);
+    let top_right = world_to_grid(center + half);
+    let tile = minimap_tile_size(&bounds);
+
+    view_query.iter_mut().for_each(|mut style| {
+        let offset = minimap_offset(&bounds, &bottom_left);
+        style.position.left = Val::Px(offset.x);
+        style.position.bottom = Val::Px(offset.y);
+        style.size = Size::new(
+            Val::Px((top_right.x - bottom_left.x + 1) as f32 * tile),
+            Val::Px((top_right.y - bottom_left.y + 1) as f32 * tile),
+        );
+    });
+}
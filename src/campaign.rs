@@ -0,0 +1,220 @@
+use crate::camera::spawn_cameras;
+use crate::consts::*;
+use crate::input::*;
+use crate::level::Level;
+use crate::storage;
+use crate::title::*;
+use crate::{GameMode, Stage, StartGame, State, StateKind};
+
+use bevy::prelude::*;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Campaign::load())
+            .add_system(level_select.label("level_select"))
+            .add_system(level_select_text)
+            .add_system(unlock_next)
+            // 島で押した Esc で、そのまま Title まで戻らないように
+            .add_system(campaign_input.after("level_select"));
+    }
+}
+
+/*
+ * Campaign: 順番に遊ぶ島
+ * 後の島ほど小さく、熊が多く、Walnut が生えにくい
+ * クリアすると次の島が遊べるようになり、CAMPAIGN_FILE に遊べる島の数を保存する
+ */
+const LEVELS: [&str; 5] = [
+    include_str!("../assets/levels/1-meadow.level"),
+    include_str!("../assets/levels/2-cove.level"),
+    include_str!("../assets/levels/3-ridge.level"),
+    include_str!("../assets/levels/4-islet.level"),
+    include_str!("../assets/levels/5-rock.level"),
+];
+
+// 遊べない島は Campaign に入れない
+fn parse_level(text: &str) -> Result<Level, String> {
+    let level = Level::parse(text)?;
+    level.validate()?;
+    Ok(level)
+}
+
+pub struct Campaign {
+    pub levels: Vec<Level>,
+    // 遊べる島の数
+    pub unlocked: usize,
+    // Level select で選んでいる島、遊んでいる島
+    pub current: usize,
+}
+
+impl Campaign {
+    fn load() -> Campaign {
+        let levels = LEVELS
+            .iter()
+            .filter_map(|text| match parse_level(text) {
+                Ok(level) => Some(level),
+                Err(e) => {
+                    warn!("broken campaign level: {}", e);
+                    None
+                }
+            })
+            .collect();
+        let unlocked = storage::read(CAMPAIGN_FILE)
+            .and_then(|text| text.trim().parse().ok())
+            .unwrap_or(1);
+        Campaign {
+            levels,
+            unlocked,
+            current: 0,
+        }
+    }
+    fn save(&self) {
+        storage::write(CAMPAIGN_FILE, &format!("{}\n", self.unlocked));
+    }
+    fn playable(&self) -> usize {
+        usize::min(self.unlocked, self.levels.len()).max(1)
+    }
+    fn start(
+        &mut self,
+        index: usize,
+        stage: &mut Stage,
+        mode: &mut GameMode,
+        start_events: &mut EventWriter<StartGame>,
+    ) {
+        self.current = index;
+        stage.level = self.levels[index].clone();
+        *mode = GameMode::Campaign;
+        start_events.send(StartGame);
+    }
+}
+
+// Title -> L で島を選ぶ画面へ。上下で選んで Enter で始める
+fn level_select(
+    mut screen: ResMut<Screen>,
+    mut campaign: ResMut<Campaign>,
+    mut stage: ResMut<Stage>,
+    mut mode: ResMut<GameMode>,
+    key: Res<Input<KeyCode>>,
+    actions: Res<Actions>,
+    mut start_events: EventWriter<StartGame>,
+) {
+    match *screen {
+        Screen::Title if key.just_pressed(KeyCode::L) => {
+            if !campaign.levels.is_empty() {
+                campaign.current = usize::min(campaign.current, campaign.playable() - 1);
+                *screen = Screen::LevelSelect;
+            }
+            return;
+        }
+        Screen::LevelSelect => {}
+        _ => return,
+    }
+    let playable = campaign.playable();
+    if actions.just_pressed(Action::MoveUp) {
+        campaign.current = (campaign.current + playable - 1) % playable;
+    }
+    if actions.just_pressed(Action::MoveDown) {
+        campaign.current = (campaign.current + 1) % playable;
+    }
+    if key.just_pressed(KeyCode::Escape) {
+        *screen = Screen::Title;
+    } else if actions.just_pressed(Action::Interact) {
+        let current = campaign.current;
+        campaign.start(current, &mut stage, &mut mode, &mut start_events);
+    }
+}
+
+fn level_select_text(
+    screen: Res<Screen>,
+    campaign: Res<Campaign>,
+    mut text_query: Query<&mut Text, With<TitleText>>,
+) {
+    if *screen != Screen::LevelSelect {
+        return;
+    }
+    let mut value = "Campaign\n\n".to_string();
+    campaign.levels.iter().enumerate().for_each(|(i, level)| {
+        let cursor = if i == campaign.current { ">" } else { " " };
+        let lock = if i < campaign.playable() {
+            ""
+        } else {
+            " (locked)"
+        };
+        value.push_str(&format!("{} {}{}\n", cursor, level.name, lock));
+    });
+    value.push_str("\nUp/Down: select  Enter: start  Esc: back");
+
+    text_query.iter_mut().for_each(|mut text| {
+        text.sections[0].value = value.clone();
+    });
+}
+
+// クリアしたら次の島を遊べるようにする
+fn unlock_next(
+    mode: Res<GameMode>,
+    mut campaign: ResMut<Campaign>,
+    state_query: Query<&State, Changed<State>>,
+) {
+    if *mode != GameMode::Campaign {
+        return;
+    }
+    let cleared = state_query
+        .iter()
+        .any(|state| state.kind == StateKind::GameClear);
+    if cleared && campaign.unlocked < campaign.current + 2 {
+        campaign.unlocked = campaign.current + 2;
+        campaign.save();
+    }
+}
+
+// クリアしたら Enter で次の島へ、Esc でいつでも島を選ぶ画面に戻る
+fn campaign_input(
+    mut commands: Commands,
+    mut screen: ResMut<Screen>,
+    mut campaign: ResMut<Campaign>,
+    mut stage: ResMut<Stage>,
+    mut mode: ResMut<GameMode>,
+    key: Res<Input<KeyCode>>,
+    actions: Res<Actions>,
+    state_query: Query<&State>,
+    entity_query: Query<Entity>,
+    asset_server: Res<AssetServer>,
+    mut start_events: EventWriter<StartGame>,
+) {
+    if *screen != Screen::Playing || *mode != GameMode::Campaign {
+        return;
+    }
+    let cleared = state_query
+        .iter()
+        .any(|state| state.kind == StateKind::GameClear);
+    let next = campaign.current + 1;
+    if cleared && actions.just_pressed(Action::Interact) && next < campaign.levels.len() {
+        campaign.start(next, &mut stage, &mut mode, &mut start_events);
+        return;
+    }
+    let finished = cleared && next >= campaign.levels.len();
+    if key.just_pressed(KeyCode::Escape) || (finished && actions.just_pressed(Action::Interact)) {
+        entity_query
+            .iter()
+            .for_each(|entity| commands.entity(entity).despawn());
+        spawn_cameras(&mut commands);
+        spawn_title(&mut commands, &asset_server);
+        *screen = Screen::LevelSelect;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn campaign_levels_are_playable() {
+        LEVELS.iter().enumerate().for_each(|(i, text)| {
+            if let Err(e) = parse_level(text) {
+                panic!("level {}: {}", i + 1, e);
+            }
+        });
+    }
+}
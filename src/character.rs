@@ -0,0 +1,48 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::mission::Objective;
+use crate::GameMode;
+
+/// Player が操作する生き物。タイトルの H で選ぶ
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Character(pub Species);
+
+impl Default for Character {
+    fn default() -> Self {
+        Character(Species::Human)
+    }
+}
+
+pub const CHARACTERS: [Species; 4] = [
+    Species::Human,
+    Species::Fox,
+    Species::StrongBear,
+    Species::Squirrel,
+];
+
+impl Character {
+    pub fn next(self) -> Character {
+        let i = CHARACTERS
+            .iter()
+            .position(|species| *species == self.0)
+            .unwrap_or(0);
+        Character(CHARACTERS[(i + 1) % CHARACTERS.len()])
+    }
+    /// Versus は船に乗る競争、通信対戦はサーバーが人間を置くので、そのときは人間
+    pub fn species(self, mode: GameMode) -> Species {
+        match mode {
+            GameMode::Normal | GameMode::Campaign | GameMode::Coop => self.0,
+            _ => Species::Human,
+        }
+    }
+}
+
+/// 生き物ごとのクリア条件。Level に objective が無いときに使う
+pub fn default_objective(species: Species) -> Objective {
+    match species {
+        Species::Fox => Objective::Survive(SURVIVE_TICKS_FOX),
+        Species::StrongBear => Objective::Reach(Species::StrongBear, TARGET_STRONG_BEAR),
+        Species::Squirrel => Objective::Eat(Species::Walnut, HOARD_SQUIRREL),
+        _ => Objective::Escape,
+    }
+}
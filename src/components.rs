@@ -44,10 +44,12 @@ impl PartialEq for Position {
         self.x == other.x && self.y == other.y
     }
 }
-pub struct GameTimer(Timer);
 
+/// 操作している人。id は 0 が 1P、1 が 2P
 #[derive(Component)]
-pub struct Player;
+pub struct Player {
+    pub id: usize,
+}
 #[derive(Component)]
 pub struct Human;
 #[derive(Component)]
@@ -58,6 +60,47 @@ pub struct WeakBear;
 pub struct Fox;
 #[derive(Component)]
 pub struct Walnut;
+/// Player が選んだときだけ島にいる
+#[derive(Component)]
+pub struct Squirrel;
+
+/// 描画などで種類ごとに扱いを変えるときに使う
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Species {
+    Human,
+    StrongBear,
+    WeakBear,
+    Fox,
+    Walnut,
+    Squirrel,
+}
+
+impl Species {
+    pub const ALL: [Species; 6] = [
+        Species::Human,
+        Species::StrongBear,
+        Species::WeakBear,
+        Species::Fox,
+        Species::Walnut,
+        Species::Squirrel,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Species::Human => "human",
+            Species::StrongBear => "strong_bear",
+            Species::WeakBear => "weak_bear",
+            Species::Fox => "fox",
+            Species::Walnut => "walnut",
+            Species::Squirrel => "squirrel",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Species> {
+        Species::ALL
+            .iter()
+            .copied()
+            .find(|species| species.name() == name)
+    }
+}
 
 #[derive(Component)]
 pub struct WalnutEater;
@@ -69,6 +112,8 @@ pub struct StrongBearEater;
 pub struct WeakBearEater;
 #[derive(Component)]
 pub struct HumanEater;
+#[derive(Component)]
+pub struct SquirrelEater;
 
 #[derive(Component)]
 pub struct WalnutPrey;
@@ -87,12 +132,10 @@ pub struct Field;
 pub struct Terminal;
 #[derive(Component)]
 pub struct Ship {
-    pub index: usize,
+    pub line: usize,
+    pub docked: bool,
 }
 
-#[derive(Component)]
-pub struct HpText;
-
 #[derive(Component)]
 pub struct Counter {
     pub val: i32,
@@ -138,6 +181,13 @@ impl HP {
             decrease: DECREASE_HP_FOX,
         };
     }
+    pub fn squirrel() -> HP {
+        return HP {
+            max: MAX_HP_SQUIRREL,
+            val: MAX_HP_SQUIRREL,
+            decrease: DECREASE_HP_SQUIRREL,
+        };
+    }
     pub fn walnut() -> HP {
         return HP {
             max: MAX_HP_WALNUT,
@@ -172,6 +222,12 @@ impl Satiety {
             val: 0.0,
         };
     }
+    pub fn squirrel() -> Satiety {
+        return Satiety {
+            max: MAX_SATIETY_SQUIRREL,
+            val: 0.0,
+        };
+    }
     pub fn walnut() -> Satiety {
         return Satiety {
             max: MAX_SATIETY_WALNUT,
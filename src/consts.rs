@@ -1,3 +1,4 @@
+use bevy::prelude::Color;
 use std::f32::INFINITY;
 
 // Display
@@ -10,6 +11,9 @@ pub const TERMINAL_LAYER: i32 = 1;
 pub const PLAYER_LAYER: i32 = 2;
 pub const BEAR_LAYER: i32 = 3;
 pub const TEXT_LAYER: i32 = 4;
+// 生き物からの相対位置
+pub const LABEL_LAYER_OFFSET: f32 = 0.5;
+pub const SPRITE_LAYER_OFFSET: f32 = 0.25;
 
 // Field
 pub const FIELD_LEFTBTM_X: i32 = 1;
@@ -35,6 +39,12 @@ pub const GAME_DEFAULT_FPS: i32 = 10;
 pub const GAME_FPS: i32 = 10;
 pub const GAME_TICK: u64 = 1000 / GAME_FPS as u64; // 10; // ms
 
+// 0.5x, 1x, 2x, 4x
+pub const GAME_SPEEDS: [f64; 4] = [0.5, 1.0, 2.0, 4.0];
+pub const GAME_DEFAULT_SPEED: usize = 1;
+// 1 フレームで追いかける tick の上限
+pub const MAX_CATCH_UP_TICKS: u32 = 8;
+
 // Age
 pub const HEALTHSPAN_STRONG_BEAR: i32 = 210 * GAME_DEFAULT_FPS; // 210 sec x 10 FPS
 pub const LIFESPAN_WEAK_BEAR: i32 = 300 * GAME_DEFAULT_FPS; // 300 sec
@@ -47,11 +57,13 @@ pub const MAX_HP_HUMAN: f32 = 100.0;
 pub const MAX_HP_BEAR: f32 = 30.0;
 pub const MAX_HP_FOX: f32 = 5.0;
 pub const MAX_HP_WALNUT: f32 = 1.0;
+pub const MAX_HP_SQUIRREL: f32 = 10.0;
 
 pub const DECREASE_HP_HUMAN: f32 = 1.0 / GAME_DEFAULT_FPS as f32; // eat fox -> 10sec eat bear-> 30sec
 pub const DECREASE_HP_BEAR: f32 = 0.3 / GAME_DEFAULT_FPS as f32; // eat fox -> 30sec
 pub const DECREASE_HP_FOX: f32 = 0.1 / GAME_DEFAULT_FPS as f32;
 pub const DECREASE_HP_WALNUT: f32 = 0.0;
+pub const DECREASE_HP_SQUIRREL: f32 = 0.2 / GAME_DEFAULT_FPS as f32; // eat walnut -> 5sec
 
 pub const INITIAL_HP_HUMAN: f32 = 50.0;
 
@@ -59,6 +71,7 @@ pub const HEALING_HP_HUMAN: f32 = 20.0;
 pub const HEALING_HP_BEAR: f32 = 30.0;
 pub const HEALING_HP_FOX: f32 = 5.0;
 pub const HEALING_HP_WALNUT: f32 = 1.0;
+pub const HEALING_HP_SQUIRREL: f32 = 3.0;
 
 // Stamina
 pub const MAX_STAMINA: i32 = 100;
@@ -68,19 +81,159 @@ pub const HEALING_STAMINA_STRONG_BEAR: i32 = 10;
 pub const HEALING_STAMINA_WEAK_BEAR: i32 = 10;
 pub const HEALING_STAMINA_FOX: i32 = 10;
 pub const HEALING_STAMINA_WALNUT: i32 = 0;
-// MAX_STAMINA / HEALING_STAMINA_SHIP / GAME_TICK_BY_SEC  * move_array_size
-// 100 / 4 / 10 * 120 = 300 sec = 5.0 min
-pub const HEALING_STAMINA_SHIP: i32 = 4;
+pub const HEALING_STAMINA_SQUIRREL: i32 = 25;
+
+// Ship
+// 5分に1回来て、30秒ぐらい滞在する
+pub const SHIP_FIRST_ARRIVAL: i32 = 210 * GAME_DEFAULT_FPS;
+pub const SHIP_INTERVAL: i32 = 300 * GAME_DEFAULT_FPS;
+pub const SHIP_DOCKING: i32 = 30 * GAME_DEFAULT_FPS;
 
 // Satiety
 pub const MAX_WALNUT_COUNT: usize = 15;
+// Walnut の木からこのマス数以内に生える
+pub const WALNUT_SOURCE_RANGE: u32 = 2;
 pub const MAX_SATIETY_HUMAN: f32 = INFINITY;
 pub const MAX_SATIETY_STRONG_BEAR: f32 = HEALING_SATIETY_FOX * 5.;
 pub const MAX_SATIETY_WEAK_BEAR: f32 = INFINITY;
 pub const MAX_SATIETY_FOX: f32 = HEALING_SATIETY_WALNUT / 2.;
 pub const MAX_SATIETY_WALNUT: f32 = INFINITY;
+pub const MAX_SATIETY_SQUIRREL: f32 = INFINITY;
 
 pub const HEALING_SATIETY_HUMAN: f32 = 8.0;
 pub const HEALING_SATIETY_BEAR: f32 = 4.0;
 pub const HEALING_SATIETY_FOX: f32 = 2.0;
 pub const HEALING_SATIETY_WALNUT: f32 = 1.0;
+pub const HEALING_SATIETY_SQUIRREL: f32 = 1.5;
+
+// Score
+pub const SCORE_TIME_LIMIT: i32 = 600 * GAME_DEFAULT_FPS;
+pub const SCORE_PER_SEC: i32 = 10;
+pub const SCORE_PER_HP: i32 = 10;
+pub const SCORE_WALNUT: i32 = 10;
+pub const SCORE_FOX: i32 = 50;
+pub const SCORE_WEAK_BEAR: i32 = 200;
+// 生態系が崩れていたら減点
+pub const SCORE_BEAR_EXTINCT: i32 = -500;
+pub const SCORE_FOX_EXTINCT: i32 = -200;
+pub const SCORE_WALNUT_EXTINCT: i32 = -100;
+
+// Character
+// 人間以外を選んだときのクリア条件
+pub const SURVIVE_TICKS_FOX: i32 = 180 * GAME_DEFAULT_FPS; // 3 min
+pub const TARGET_STRONG_BEAR: i32 = 5;
+pub const HOARD_SQUIRREL: i32 = 15;
+
+// Stage
+pub const DEFAULT_LEVEL: &str = "island";
+pub const DEFAULT_SEED: u64 = 0;
+pub const CUSTOM_LEVEL: &str = "custom";
+pub const CUSTOM_LEVEL_FILE: &str = "custom.level";
+pub const PROCEDURAL_LEVEL: &str = "procedural";
+
+// Difficulty
+// Easy, Normal, Hard の順に掛ける
+pub const DIFFICULTY_HUNGER: [f32; 3] = [0.7, 1.0, 1.4];
+pub const DIFFICULTY_BEAR_STAMINA: [f32; 3] = [0.8, 1.0, 1.3];
+pub const DIFFICULTY_BEARS: [f32; 3] = [0.5, 1.0, 2.0];
+pub const DIFFICULTY_WALNUT: [f32; 3] = [1.5, 1.0, 0.6];
+// 小さいほど船がよく来る
+pub const DIFFICULTY_SHIP_INTERVAL: [f32; 3] = [0.8, 1.0, 1.25];
+
+// Campaign
+// クリアして遊べるようになった島の数
+pub const CAMPAIGN_FILE: &str = "campaign.txt";
+
+// Island Generator
+pub const ISLAND_NOISE_CELL: i32 = 4;
+// noise がどれだけ海岸線を崩すか
+pub const ISLAND_ROUGHNESS: f32 = 0.6;
+pub const ISLAND_SEA_LEVEL: f32 = 0.25;
+pub const ISLAND_MIN_FIELDS: usize = 120;
+pub const ISLAND_ATTEMPTS: u32 = 10;
+
+// Storage
+// wasm では localStorage のキーにこれを付ける
+pub const STORAGE_PREFIX: &str = "food-chain-game/";
+
+// High Score
+pub const HIGHSCORE_NUM: usize = 10;
+pub const HIGHSCORE_FILE: &str = "highscores.txt";
+// wasm の localStorage では前からこのキーに保存している
+pub const HIGHSCORE_STORAGE_KEY: &str = "food-chain-game.highscores";
+pub const HIGHSCORE_SALT: &str = "food-chain-game";
+
+// Input
+pub const BINDINGS_FILE: &str = "bindings.txt";
+// スティックをこれ以上倒したら入力とみなす
+pub const GAMEPAD_DEADZONE: f32 = 0.5;
+// 動けない間に押した移動をいくつまで覚えておくか (bindings.txt の buffer で変えられる)
+pub const INPUT_BUFFER_LEN: usize = 2;
+
+// D-pad
+pub const DPAD_BUTTON_SIZE: f32 = 36.0;
+pub const DPAD_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.2);
+pub const DPAD_PRESSED_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.5);
+
+// Camera
+pub const CAMERA_MIN_ZOOM: f32 = 0.5;
+pub const CAMERA_MAX_ZOOM: f32 = 2.0;
+pub const CAMERA_ZOOM_STEP: f32 = 1.1;
+// px / sec
+pub const CAMERA_PAN_SPEED: f32 = 400.0;
+
+// Minimap
+// 1 マスの大きさ (px)。大きな島は MINIMAP_MAX_SIZE に収まるよう縮める
+pub const MINIMAP_TILE: f32 = 3.0;
+pub const MINIMAP_MAX_SIZE: f32 = 100.0;
+
+// HUD
+pub const HUD_TOP: f32 = 40.0;
+pub const HUD_ROW_HEIGHT: f32 = 40.0;
+pub const HUD_FONT_SIZE: f32 = 14.0;
+pub const HUD_BAR_WIDTH: f32 = 100.0;
+pub const HUD_BAR_HEIGHT: f32 = 8.0;
+pub const HUD_MAX_SATIETY: f32 = HEALING_SATIETY_BEAR * 5.;
+
+// Inventory
+// 袋に入れておける食べ物の数
+pub const BAG_SIZE: usize = 3;
+
+// Crafting
+pub const INITIAL_STICK_NUM: u32 = 8;
+pub const INITIAL_STONE_NUM: u32 = 4;
+pub const MAX_MATERIAL_COUNT: usize = 12;
+// probability (5% = 500)
+pub const PROBABILITY_INCREASE_MATERIAL: u32 = 50;
+// 作るのに要る (stick, stone, stamina)。Stamina は MAX_STAMINA で 1 歩ぶん
+pub const RECIPE_SPEAR: (u32, u32, i32) = (2, 1, 3 * MAX_STAMINA);
+pub const RECIPE_TRAP: (u32, u32, i32) = (2, 2, 2 * MAX_STAMINA);
+pub const RECIPE_RAFT: (u32, u32, i32) = (6, 0, 10 * MAX_STAMINA);
+// 槍で StrongBear を倒せる回数
+pub const SPEAR_USES: u32 = 2;
+pub const STICK_COLOR: Color = Color::rgb(0.55, 0.35, 0.15);
+pub const STONE_COLOR: Color = Color::GRAY;
+
+// Multiplayer
+pub const MAX_PLAYERS: usize = 2;
+// 1P, 2P の色
+pub const PLAYER_COLORS: [Color; MAX_PLAYERS] = [Color::PURPLE, Color::PINK];
+
+// Network
+pub const NET_DEFAULT_PORT: u16 = 8080;
+pub const NET_DEFAULT_URL: &str = "ws://127.0.0.1:8080";
+// 接続先を変えたいときはここに URL を書く
+pub const NET_SERVER_FILE: &str = "server.txt";
+// 通信のスレッドが届いたものを見に行く間隔
+pub const NET_POLL_MS: u64 = 5;
+// サーバーが 1 フレームを回す間隔
+pub const NET_SERVER_FRAME_MS: u64 = 5;
+
+// Label
+pub const LABEL_FONT_SIZE: f32 = 10.0;
+
+// Sprite
+pub const SPRITE_SIZE: f32 = 16.0;
+pub const SPRITE_FRAMES: usize = 4;
+pub const SPRITE_ROWS: usize = 3;
+pub const SPRITE_FRAME_SECS: f32 = 0.15;
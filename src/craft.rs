@@ -0,0 +1,429 @@
+use crate::bag::Bag;
+use crate::components::*;
+use crate::consts::*;
+use crate::eat::{nutrition, Meals};
+use crate::editor::marker_color;
+use crate::input::*;
+use crate::level::Level;
+use crate::mission::Mission;
+use crate::score::Record;
+use crate::simulation::*;
+use crate::title::Screen;
+use crate::{get_random_grid, get_render_position, GameMode, Stage, Stamina};
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::prelude::*;
+use std::collections::HashSet;
+
+pub struct CraftPlugin;
+
+impl Plugin for CraftPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(use_tools)
+            .add_system_set_to_stage(SimulationStage, tick_set(Tick::Input).with_system(craft))
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Eat)
+                    .with_system(gather)
+                    .with_system(stab_strong_bear.label("spear"))
+                    .with_system(trap_fox)
+                    .with_system(collect_trap),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Reproduce).with_system(increase_material),
+            );
+    }
+}
+
+/*
+ * Craft: 人間の Player だけが島に落ちている材料で道具を作れる
+ * Stick, Stone は踏めば拾う。作るとその分 Stamina が減って、しばらく動けない
+ *   Spear: StrongBear に食べられる代わりに倒す。SPEAR_USES 回で折れる
+ *   Trap : その場に置く。Fox がかかったら拾いに行って食べる
+ *   Raft : 海岸で作り、できあがったら島を出る。船と同じく escape になる
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Material {
+    Stick,
+    Stone,
+}
+
+impl Material {
+    pub const ALL: [Material; 2] = [Material::Stick, Material::Stone];
+}
+
+/// 島に落ちている材料
+#[derive(Component)]
+pub struct Pile(pub Material);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Recipe {
+    Spear,
+    Trap,
+    Raft,
+}
+
+impl Recipe {
+    pub const ALL: [Recipe; 3] = [Recipe::Spear, Recipe::Trap, Recipe::Raft];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Recipe::Spear => "spear",
+            Recipe::Trap => "trap",
+            Recipe::Raft => "raft",
+        }
+    }
+    // (stick, stone, stamina)
+    fn cost(&self) -> (u32, u32, i32) {
+        match self {
+            Recipe::Spear => RECIPE_SPEAR,
+            Recipe::Trap => RECIPE_TRAP,
+            Recipe::Raft => RECIPE_RAFT,
+        }
+    }
+}
+
+/// 人間の Player が拾った材料と作った道具
+#[derive(Component)]
+pub struct Tools {
+    pub sticks: u32,
+    pub stones: u32,
+    // 槍の残りの使用回数
+    pub spear: u32,
+    // 作りかけの Raft がある。Stamina が戻ったら島を出る
+    pub raft: bool,
+    // Raft で島を出た。goal が船に乗ったのと同じに扱う
+    pub afloat: bool,
+    pub recipe: Recipe,
+    // 次の tick で recipe を作る
+    craft_requested: bool,
+}
+
+impl Default for Tools {
+    fn default() -> Self {
+        Tools {
+            sticks: 0,
+            stones: 0,
+            spear: 0,
+            raft: false,
+            afloat: false,
+            recipe: Recipe::Spear,
+            craft_requested: false,
+        }
+    }
+}
+
+impl Tools {
+    pub fn can_afford(&self, recipe: Recipe) -> bool {
+        let (sticks, stones, _) = recipe.cost();
+        self.sticks >= sticks && self.stones >= stones
+    }
+}
+
+/// 道具を作れるのは手元で島を動かしているときだけ。通信対戦の島はサーバーのもの
+pub fn crafting(mode: GameMode) -> bool {
+    matches!(
+        mode,
+        GameMode::Normal | GameMode::Campaign | GameMode::Coop | GameMode::Versus
+    )
+}
+
+// 上下左右のどこかが海のマス
+fn on_shore(level: &Level, pos: &Position) -> bool {
+    [
+        Position::up(),
+        Position::down(),
+        Position::left(),
+        Position::right(),
+    ]
+    .iter()
+    .any(|dir| !level.is_field(&(pos + dir)))
+}
+
+/// 最初に島に落ちている材料。initial_spawn の rng を使うので seed が同じなら同じ場所
+pub fn spawn_materials<R: Rng>(commands: &mut Commands, level: &Level, rng: &mut R) {
+    [
+        (Material::Stick, INITIAL_STICK_NUM),
+        (Material::Stone, INITIAL_STONE_NUM),
+    ]
+    .iter()
+    .for_each(|(material, count)| {
+        for _ in 0..*count {
+            if let Some(grid) = get_random_grid(level, rng) {
+                spawn_pile(commands, *material, grid);
+            }
+        }
+    });
+}
+
+fn spawn_pile(commands: &mut Commands, material: Material, grid: Position) -> Entity {
+    let position = Position {
+        z: TERMINAL_LAYER,
+        ..grid
+    };
+    let render_pos = get_render_position(&position);
+    let transform = Transform {
+        translation: Vec3::new(render_pos.x as f32, render_pos.y as f32, 0.0),
+        ..Default::default()
+    };
+    let mut pile = match material {
+        Material::Stick => commands.spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Rectangle {
+                extents: Vec2::new(UNIT_WIDTH as f32 * 0.8, UNIT_HEIGHT as f32 * 0.2),
+                ..shapes::Rectangle::default()
+            },
+            DrawMode::Fill(FillMode::color(STICK_COLOR)),
+            transform,
+        )),
+        Material::Stone => commands.spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Circle {
+                radius: (UNIT_WIDTH / 4) as f32,
+                center: Vec2::new(0.0, 0.0),
+            },
+            DrawMode::Fill(FillMode::color(STONE_COLOR)),
+            transform,
+        )),
+    };
+    pile.insert(Pile(material)).insert(position).id()
+}
+
+/// 置いた Trap。かかった Fox は Player が拾うまで残る
+#[derive(Component)]
+pub struct Trap {
+    pub caught: bool,
+}
+
+fn spawn_trap(commands: &mut Commands, grid: Position) {
+    let position = Position {
+        z: TERMINAL_LAYER,
+        ..grid
+    };
+    let render_pos = get_render_position(&position);
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Rectangle {
+                extents: Vec2::new(UNIT_WIDTH as f32 * 0.8, UNIT_HEIGHT as f32 * 0.8),
+                ..shapes::Rectangle::default()
+            },
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(Color::NONE),
+                outline_mode: StrokeMode::new(STONE_COLOR, 2.0),
+            },
+            Transform {
+                translation: Vec3::new(render_pos.x as f32, render_pos.y as f32, 0.0),
+                ..Default::default()
+            },
+        ))
+        .insert(Trap { caught: false })
+        .insert(position);
+}
+
+// Recipe: 作るものを選ぶ, Craft: 選んだものを作る
+fn use_tools(
+    screen: Res<Screen>,
+    mode: Res<GameMode>,
+    actions: Res<Actions>,
+    mut tools_query: Query<(&Player, &mut Tools)>,
+) {
+    if *screen != Screen::Playing || !crafting(*mode) {
+        return;
+    }
+    tools_query.iter_mut().for_each(|(player, mut tools)| {
+        if actions.just_pressed(Action::Recipe.for_player(player.id)) {
+            let i = Recipe::ALL
+                .iter()
+                .position(|recipe| *recipe == tools.recipe)
+                .unwrap_or(0);
+            tools.recipe = Recipe::ALL[(i + 1) % Recipe::ALL.len()];
+        }
+        if actions.just_pressed(Action::Craft.for_player(player.id)) {
+            tools.craft_requested = true;
+        }
+    });
+}
+
+fn craft(
+    mut commands: Commands,
+    stage: Res<Stage>,
+    mission: Res<Mission>,
+    mut tools_query: Query<(&Position, &HP, &mut Stamina, &mut Tools)>,
+    trap_query: Query<&Position, With<Trap>>,
+) {
+    tools_query
+        .iter_mut()
+        .for_each(|(pos, hp, mut stamina, mut tools)| {
+            let requested = std::mem::take(&mut tools.craft_requested);
+            // Raft ができあがったら漕ぎ出す
+            if tools.raft && !tools.afloat && stamina.can_move() {
+                tools.afloat = true;
+                stamina.val = 0;
+                return;
+            }
+            if !requested || hp.val <= 0.0 || tools.raft || !stamina.can_move() {
+                return;
+            }
+            let recipe = tools.recipe;
+            if !tools.can_afford(recipe) {
+                return;
+            }
+            match recipe {
+                Recipe::Spear => tools.spear += SPEAR_USES,
+                Recipe::Trap => {
+                    if trap_query.iter().any(|trap| trap == pos) {
+                        return;
+                    }
+                    spawn_trap(&mut commands, *pos);
+                }
+                // 船で出なくてよいミッションでは作らない
+                Recipe::Raft => {
+                    if !mission.needs_ship() || !on_shore(&stage.level, pos) {
+                        return;
+                    }
+                    tools.raft = true;
+                }
+            }
+            let (sticks, stones, cost) = recipe.cost();
+            tools.sticks -= sticks;
+            tools.stones -= stones;
+            stamina.val -= cost;
+        });
+}
+
+// 踏んだ材料を拾う。同じ tick に 2 人が踏んだら先の人のもの
+fn gather(
+    mut commands: Commands,
+    mut tools_query: Query<(&Position, &HP, &mut Tools)>,
+    pile_query: Query<(Entity, &Position, &Pile)>,
+) {
+    let mut taken = HashSet::new();
+    tools_query.iter_mut().for_each(|(pos, hp, mut tools)| {
+        if hp.val <= 0.0 {
+            return;
+        }
+        pile_query
+            .iter()
+            .filter(|(_, p_pos, _)| *p_pos == pos)
+            .for_each(|(pile, _, material)| {
+                if !taken.insert(pile) {
+                    return;
+                }
+                match material.0 {
+                    Material::Stick => tools.sticks += 1,
+                    Material::Stone => tools.stones += 1,
+                }
+                commands.entity(pile).despawn_recursive();
+            });
+    });
+}
+
+// 槍を持っていれば StrongBear を倒す。倒された熊は eaten_human で人間を食べない
+fn stab_strong_bear(
+    mut tools_query: Query<(&Position, &HP, &mut Tools)>,
+    mut strong_bear_query: Query<(&Position, &mut HP), (With<StrongBear>, Without<Tools>)>,
+) {
+    tools_query.iter_mut().for_each(|(pos, hp, mut tools)| {
+        if hp.val <= 0.0 {
+            return;
+        }
+        strong_bear_query
+            .iter_mut()
+            .filter(|(b_pos, b_hp)| *b_pos == pos && b_hp.val > 0.0)
+            .for_each(|(_, mut b_hp)| {
+                if tools.spear > 0 {
+                    tools.spear -= 1;
+                    b_hp.val = 0.0;
+                }
+            });
+    });
+}
+
+fn trap_fox(
+    mut trap_query: Query<(&Position, &mut Trap, &mut DrawMode)>,
+    mut fox_query: Query<(&Position, &mut HP), (With<Fox>, Without<Player>)>,
+) {
+    trap_query
+        .iter_mut()
+        .filter(|(_, trap, _)| !trap.caught)
+        .for_each(|(t_pos, mut trap, mut draw_mode)| {
+            if let Some((_, mut f_hp)) = fox_query
+                .iter_mut()
+                .find(|(f_pos, f_hp)| *f_pos == t_pos && f_hp.val > 0.0)
+            {
+                f_hp.val = 0.0;
+                trap.caught = true;
+                *draw_mode = DrawMode::Outlined {
+                    fill_mode: FillMode::color(marker_color(Species::Fox)),
+                    outline_mode: StrokeMode::new(STONE_COLOR, 2.0),
+                };
+            }
+        });
+}
+
+// かかった Fox は食べるか、袋に入れる
+fn collect_trap(
+    mut commands: Commands,
+    mut player_query: Query<
+        (Entity, &Position, &mut HP, &mut Satiety, Option<&mut Bag>),
+        With<Tools>,
+    >,
+    trap_query: Query<(Entity, &Position, &Trap)>,
+    mut record: ResMut<Record>,
+    mut meals: ResMut<Meals>,
+) {
+    let mut taken = HashSet::new();
+    player_query
+        .iter_mut()
+        .for_each(|(player, pos, mut hp, mut satiety, mut bag)| {
+            if hp.val <= 0.0 {
+                return;
+            }
+            trap_query
+                .iter()
+                .filter(|(_, t_pos, t)| t.caught && *t_pos == pos)
+                .for_each(|(trap, _, _)| {
+                    if !taken.insert(trap) {
+                        return;
+                    }
+                    commands.entity(trap).despawn_recursive();
+                    if bag.as_mut().map_or(false, |bag| bag.stash(Species::Fox)) {
+                        return;
+                    }
+                    let (healing_hp, healing_satiety) = nutrition(Species::Fox);
+                    let before = hp.val;
+                    hp.val = f32::min(hp.val + healing_hp, hp.max);
+                    satiety.val += healing_satiety;
+                    record.add(Species::Fox);
+                    meals.add(player, hp.val - before, healing_satiety);
+                });
+        });
+}
+
+// 拾われた材料はときどき島のどこかに落ちてくる
+fn increase_material(
+    mut commands: Commands,
+    stage: Res<Stage>,
+    mode: Res<GameMode>,
+    tools_query: Query<&Tools>,
+    pile_query: Query<&Position, With<Pile>>,
+) {
+    if !crafting(*mode) || tools_query.is_empty() {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    if pile_query.iter().count() >= MAX_MATERIAL_COUNT
+        || rng.gen_range(1..=10000) > PROBABILITY_INCREASE_MATERIAL
+    {
+        return;
+    }
+    let grid = match get_random_grid(&stage.level, &mut rng) {
+        Some(grid) => grid,
+        None => return,
+    };
+    if pile_query.iter().any(|pos| *pos == grid) {
+        return;
+    }
+    if let Some(material) = Material::ALL.choose(&mut rng) {
+        spawn_pile(&mut commands, *material, grid);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::ship::ShipLine;
+use crate::simulation::*;
+use crate::Stamina;
+
+use bevy::prelude::*;
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Difficulty::Normal)
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Input).with_system(apply_difficulty),
+            );
+    }
+}
+
+/// タイトルの D で選ぶ難しさ。島の生き物と船の来かたを変える
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        Difficulty::ALL
+            .iter()
+            .copied()
+            .find(|difficulty| difficulty.name() == name)
+    }
+    pub fn next(self) -> Difficulty {
+        Difficulty::ALL[(self as usize + 1) % Difficulty::ALL.len()]
+    }
+    pub fn bears(&self, count: u32) -> u32 {
+        (count as f32 * DIFFICULTY_BEARS[*self as usize]).ceil() as u32
+    }
+    pub fn walnut_rate(&self, rate: u32) -> u32 {
+        (rate as f32 * DIFFICULTY_WALNUT[*self as usize]).round() as u32
+    }
+    /// 船の間隔を変えた時刻表。停泊している長さはそのまま
+    pub fn ship_lines(&self, lines: &[ShipLine]) -> Vec<ShipLine> {
+        let scale = DIFFICULTY_SHIP_INTERVAL[*self as usize];
+        lines
+            .iter()
+            .map(|line| ShipLine {
+                first_arrival: (line.first_arrival as f32 * scale).round() as i32,
+                interval: ((line.interval as f32 * scale).round() as i32).max(line.docking + 1),
+                ..line.clone()
+            })
+            .collect()
+    }
+}
+
+// 生まれた人間と StrongBear に難しさを反映する
+fn apply_difficulty(
+    difficulty: Res<Difficulty>,
+    mut human_query: Query<&mut HP, Added<Human>>,
+    // Player の熊は難しさで弱くしない
+    mut strong_bear_query: Query<&mut Stamina, (Added<StrongBear>, Without<Player>)>,
+) {
+    let i = *difficulty as usize;
+    human_query.iter_mut().for_each(|mut hp| {
+        hp.decrease = DECREASE_HP_HUMAN * DIFFICULTY_HUNGER[i];
+    });
+    strong_bear_query.iter_mut().for_each(|mut stamina| {
+        stamina.healing_val =
+            (HEALING_STAMINA_STRONG_BEAR as f32 * DIFFICULTY_BEAR_STAMINA[i]).round() as i32;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::grid;
+
+    #[test]
+    fn ship_lines_scale_the_interval_but_not_the_docking() {
+        let line = ShipLine {
+            terminal: grid(0, 0),
+            route: vec![],
+            first_arrival: 1000,
+            interval: 2000,
+            docking: 300,
+        };
+        let normal = Difficulty::Normal.ship_lines(std::slice::from_ref(&line));
+        assert_eq!(normal[0].first_arrival, 1000);
+        assert_eq!(normal[0].interval, 2000);
+
+        let hard = Difficulty::Hard.ship_lines(std::slice::from_ref(&line));
+        assert_eq!(hard[0].first_arrival, 1250);
+        assert_eq!(hard[0].interval, 2500);
+        assert_eq!(hard[0].docking, 300);
+
+        // 縮めても停泊が終わる前に次の船は来ない
+        let short = ShipLine {
+            interval: 310,
+            ..line
+        };
+        let easy = Difficulty::Easy.ship_lines(&[short]);
+        assert_eq!(easy[0].interval, 301);
+    }
+
+    #[test]
+    fn difficulty_scales_island_bears_but_not_the_player() {
+        let mut world = World::new();
+        world.insert_resource(Difficulty::Hard);
+        let bear = world
+            .spawn()
+            .insert(StrongBear)
+            .insert(Stamina::strong_bear())
+            .id();
+        let player = world
+            .spawn()
+            .insert(StrongBear)
+            .insert(Player { id: 0 })
+            .insert(Stamina::strong_bear())
+            .id();
+
+        let mut stage = SystemStage::single(apply_difficulty);
+        stage.run(&mut world);
+
+        let hard = (HEALING_STAMINA_STRONG_BEAR as f32 * DIFFICULTY_BEAR_STAMINA[2]).round() as i32;
+        assert_eq!(world.get::<Stamina>(bear).unwrap().healing_val, hard);
+        assert_eq!(
+            world.get::<Stamina>(player).unwrap().healing_val,
+            HEALING_STAMINA_STRONG_BEAR
+        );
+    }
+}
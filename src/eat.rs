@@ -1,44 +1,70 @@
+use crate::bag::Bag;
 use crate::components::*;
 use crate::consts::*;
+use crate::score::Record;
+use crate::simulation::*;
+use crate::GameMode;
 use bevy::prelude::*;
 use std::cmp::min;
+use std::collections::HashMap;
 
 use bevy::ecs::*;
 use bevy::prelude::*;
 use rand::prelude::*;
 // https://docs.rs/bevy_prototype_lyon/latest/bevy_prototype_lyon/
 use bevy_prototype_lyon::prelude::*;
+
 pub struct HpPlugin;
-struct StomachTimer(Timer);
 
 /// 自作の Plugin に Plugin トレイトを実装すれば、Plugin として使用できる
 /// Plugin トレイトでは App Builder に必要な要素を追加するだけで良い
 impl Plugin for HpPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(StomachTimer(Timer::new(
-            std::time::Duration::from_millis(GAME_TICK),
-            true,
-        )))
-        .add_system(stomach_timer)
-        .add_system(hungry)
-        .add_system_set(
-            SystemSet::new()
-                .label("eat")
-                .with_system(eat_walnut)
-                .with_system(eat_fox)
-                .with_system(eat_weak_bear)
-                .with_system(eat_human),
-        )
-        .add_system_set(
-            SystemSet::new()
-                .after("eat")
-                .label("eaten")
-                .with_system(eaten_walnut)
-                .with_system(eaten_fox)
-                .with_system(eaten_strong_bear)
-                .with_system(eaten_weak_bear)
-                .with_system(eaten_human),
-        );
+        app.insert_resource(Meals::default())
+            .add_system_set_to_stage(SimulationStage, tick_set(Tick::Age).with_system(hungry))
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Eat)
+                    .with_system(eat_walnut)
+                    .with_system(eat_fox)
+                    .with_system(eat_weak_bear)
+                    .with_system(eat_human.after("spear"))
+                    .with_system(eat_squirrel),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Eaten)
+                    .with_system(eaten_walnut)
+                    .with_system(eaten_fox)
+                    .with_system(eaten_strong_bear)
+                    .with_system(eaten_weak_bear)
+                    .with_system(eaten_human)
+                    .with_system(eaten_squirrel)
+                    .with_system(share_meals),
+            );
+    }
+}
+
+/// この tick に Player が食べて増えた HP と満腹度
+#[derive(Default)]
+pub struct Meals(HashMap<Entity, (f32, f32)>);
+
+impl Meals {
+    pub fn add(&mut self, player: Entity, hp: f32, satiety: f32) {
+        let meal = self.0.entry(player).or_insert((0.0, 0.0));
+        meal.0 += hp;
+        meal.1 += satiety;
+    }
+}
+
+/// 食べたときに増える HP と満腹度
+pub fn nutrition(species: Species) -> (f32, f32) {
+    match species {
+        Species::Walnut => (HEALING_HP_WALNUT, HEALING_SATIETY_WALNUT),
+        Species::Fox => (HEALING_HP_FOX, HEALING_SATIETY_FOX),
+        Species::WeakBear => (HEALING_HP_BEAR, HEALING_SATIETY_BEAR),
+        Species::Squirrel => (HEALING_HP_SQUIRREL, HEALING_SATIETY_SQUIRREL),
+        _ => (0.0, 0.0),
     }
 }
 
@@ -52,19 +78,7 @@ impl Plugin for HpPlugin {
  * Ship: 5分に1回来る。30秒ぐらい滞在
  * Player: 止まってたら1分ぐらいで死ぬ。島を頑張って回る必要あり。島は端から端まで10秒ぐらいで移動可?
  */
-fn stomach_timer(time: Res<Time>, mut timer: ResMut<StomachTimer>) {
-    timer.0.tick(time.delta());
-}
-
-fn hungry(
-    mut commands: Commands,
-    timer: ResMut<StomachTimer>,
-    mut food_query: Query<(Entity, &mut HP)>,
-) {
-    if !timer.0.finished() {
-        return;
-    }
-
+fn hungry(mut commands: Commands, mut food_query: Query<(Entity, &mut HP)>) {
     food_query
         .iter_mut()
         .for_each(|(entity, mut hp)| hp.val -= hp.decrease)
@@ -85,24 +99,42 @@ fn eaten_walnut(
 
 fn eat_walnut(
     mut eater_query: Query<
-        (Entity, &Position, &mut HP, &mut Satiety),
+        (
+            Entity,
+            &Position,
+            &mut HP,
+            &mut Satiety,
+            Option<&Player>,
+            Option<&mut Bag>,
+        ),
         (With<WalnutEater>, Without<Walnut>),
     >,
     walnut_query: Query<(Entity, &Position, &HP), With<Walnut>>,
+    mut record: ResMut<Record>,
+    mut meals: ResMut<Meals>,
 ) {
     eater_query
         .iter_mut()
-        .for_each(|(_, e_pos, mut e_hp, mut e_sat)| {
+        .for_each(|(eater, e_pos, mut e_hp, mut e_sat, player, mut bag)| {
             walnut_query.iter().for_each(|(_, w_pos, _)| {
                 if e_pos == w_pos {
+                    // 袋に入れたら今は食べない
+                    if bag.as_mut().map_or(false, |bag| bag.stash(Species::Walnut)) {
+                        return;
+                    }
+                    let before = e_hp.val;
                     e_hp.val = f32::min(e_hp.val + HEALING_HP_WALNUT, e_hp.max);
                     e_sat.val += HEALING_SATIETY_WALNUT;
+                    if player.is_some() {
+                        record.walnut += 1;
+                        meals.add(eater, e_hp.val - before, HEALING_SATIETY_WALNUT);
+                    }
                 }
             })
         })
 }
 
-fn eaten_fox(
+pub fn eaten_fox(
     eater_query: Query<(Entity, &Position, &HP), With<FoxEater>>,
     mut fox_query: Query<(Entity, &Position, &mut HP), (With<Fox>, Without<FoxEater>)>,
 ) {
@@ -117,18 +149,35 @@ fn eaten_fox(
 
 fn eat_fox(
     mut eater_query: Query<
-        (Entity, &Position, &mut HP, &mut Satiety),
+        (
+            Entity,
+            &Position,
+            &mut HP,
+            &mut Satiety,
+            Option<&Player>,
+            Option<&mut Bag>,
+        ),
         (With<FoxEater>, Without<Fox>),
     >,
     fox_query: Query<(Entity, &Position, &HP), With<Fox>>,
+    mut record: ResMut<Record>,
+    mut meals: ResMut<Meals>,
 ) {
     eater_query
         .iter_mut()
-        .for_each(|(_, e_pos, mut e_hp, mut e_sat)| {
+        .for_each(|(eater, e_pos, mut e_hp, mut e_sat, player, mut bag)| {
             fox_query.iter().for_each(|(_, f_pos, _)| {
                 if e_pos == f_pos {
+                    if bag.as_mut().map_or(false, |bag| bag.stash(Species::Fox)) {
+                        return;
+                    }
+                    let before = e_hp.val;
                     e_hp.val = f32::min(e_hp.val + HEALING_HP_FOX, e_hp.max);
                     e_sat.val += HEALING_SATIETY_FOX;
+                    if player.is_some() {
+                        record.fox += 1;
+                        meals.add(eater, e_hp.val - before, HEALING_SATIETY_FOX);
+                    }
                 }
             })
         })
@@ -170,18 +219,38 @@ fn eaten_weak_bear(
 
 fn eat_weak_bear(
     mut eater_query: Query<
-        (Entity, &Position, &mut HP, &mut Satiety),
+        (
+            Entity,
+            &Position,
+            &mut HP,
+            &mut Satiety,
+            Option<&Player>,
+            Option<&mut Bag>,
+        ),
         (With<WeakBearEater>, Without<WeakBear>),
     >,
     weak_bear_query: Query<(Entity, &Position, &HP), With<WeakBear>>,
+    mut record: ResMut<Record>,
+    mut meals: ResMut<Meals>,
 ) {
     eater_query
         .iter_mut()
-        .for_each(|(_, e_pos, mut e_hp, mut e_sat)| {
-            weak_bear_query.iter().for_each(|(_, b_pos, b_hp)| {
+        .for_each(|(eater, e_pos, mut e_hp, mut e_sat, player, mut bag)| {
+            weak_bear_query.iter().for_each(|(_, b_pos, _)| {
                 if b_pos == e_pos {
+                    if bag
+                        .as_mut()
+                        .map_or(false, |bag| bag.stash(Species::WeakBear))
+                    {
+                        return;
+                    }
+                    let before = e_hp.val;
                     e_hp.val = f32::min(e_hp.val + HEALING_HP_BEAR, e_hp.max);
-                    e_sat.val += HEALING_SATIETY_BEAR
+                    e_sat.val += HEALING_SATIETY_BEAR;
+                    if player.is_some() {
+                        record.weak_bear += 1;
+                        meals.add(eater, e_hp.val - before, HEALING_SATIETY_BEAR);
+                    }
                 }
             })
         })
@@ -193,7 +262,8 @@ fn eaten_human(
 ) {
     human_query.iter_mut().for_each(|(_, h_pos, mut h_hp)| {
         eater_query.iter().for_each(|(_, e_pos, e_hp)| {
-            if h_pos == e_pos {
+            // 槍で倒された熊には食べられない
+            if h_pos == e_pos && e_hp.val > 0.0 {
                 h_hp.val = 0.0;
             }
         });
@@ -210,11 +280,77 @@ fn eat_human(
     eater_query
         .iter_mut()
         .for_each(|(_, e_pos, mut e_hp, mut e_sat)| {
-            human_query.iter().for_each(|(_, h_pos, h_hp)| {
-                if h_pos == e_pos {
+            human_query.iter().for_each(|(_, h_pos, _)| {
+                if h_pos == e_pos && e_hp.val > 0.0 {
                     e_hp.val += HEALING_HP_HUMAN;
                     e_sat.val += HEALING_SATIETY_HUMAN;
                 }
             })
         })
 }
+
+fn eaten_squirrel(
+    eater_query: Query<(Entity, &Position, &HP), With<SquirrelEater>>,
+    mut squirrel_query: Query<
+        (Entity, &Position, &mut HP),
+        (With<Squirrel>, Without<SquirrelEater>),
+    >,
+) {
+    squirrel_query.iter_mut().for_each(|(_, s_pos, mut s_hp)| {
+        eater_query.iter().for_each(|(_, e_pos, _)| {
+            if s_pos == e_pos {
+                s_hp.val = 0.0;
+            }
+        });
+    });
+}
+
+fn eat_squirrel(
+    mut eater_query: Query<
+        (Entity, &Position, &mut HP, &mut Satiety),
+        (With<SquirrelEater>, Without<Squirrel>),
+    >,
+    squirrel_query: Query<(Entity, &Position, &HP), With<Squirrel>>,
+) {
+    eater_query
+        .iter_mut()
+        .for_each(|(_, e_pos, mut e_hp, mut e_sat)| {
+            squirrel_query.iter().for_each(|(_, s_pos, _)| {
+                if s_pos == e_pos {
+                    e_hp.val = f32::min(e_hp.val + HEALING_HP_SQUIRREL, e_hp.max);
+                    e_sat.val += HEALING_SATIETY_SQUIRREL;
+                }
+            })
+        })
+}
+
+// Coop では Player が食べた分を、生きている Player みんなで分け合う
+// Versus では食べた人のもの
+fn share_meals(
+    mode: Res<GameMode>,
+    mut meals: ResMut<Meals>,
+    mut player_query: Query<(Entity, &mut HP, &mut Satiety), With<Player>>,
+) {
+    let meals = std::mem::take(&mut meals.0);
+    if *mode != GameMode::Coop || meals.is_empty() {
+        return;
+    }
+    let alive = player_query
+        .iter()
+        .filter(|(_, hp, _)| hp.val > 0.0)
+        .count();
+    if alive == 0 {
+        return;
+    }
+    let (hp_total, satiety_total) = meals.values().fold((0.0, 0.0), |(hp, satiety), meal| {
+        (hp + meal.0, satiety + meal.1)
+    });
+    player_query
+        .iter_mut()
+        .filter(|(_, hp, _)| hp.val > 0.0)
+        .for_each(|(player, mut hp, mut satiety)| {
+            let (hp_own, satiety_own) = meals.get(&player).copied().unwrap_or((0.0, 0.0));
+            hp.val = f32::min(hp.val - hp_own + hp_total / alive as f32, hp.max);
+            satiety.val += satiety_total / alive as f32 - satiety_own;
+        });
+}
@@ -0,0 +1,461 @@
+use crate::camera::{cursor_grid, spawn_cameras, CameraQuery};
+use crate::components::*;
+use crate::consts::*;
+use crate::level::*;
+use crate::ship::ShipLine;
+use crate::storage;
+use crate::title::*;
+use crate::{GameMode, Stage, StartGame};
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+pub struct EditorPlugin;
+
+/*
+ * Level エディタ
+ * 道具を選んで 左クリックで置く / 右クリックで消す
+ *   F: 島  T: Terminal (船を 1 隻増やす)  V: 航路  H: Player  W: Walnut の木
+ *   1~4: walnut / fox / strong_bear / weak_bear
+ * Tab: 航路を編集する船を切り替える
+ * S: 保存  L: 読み込み  Enter: 試しに遊ぶ  Esc: タイトルに戻る
+ */
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Editor {
+            level: Level::empty(CUSTOM_LEVEL),
+            tool: Tool::Field,
+            line: 0,
+            message: String::new(),
+            dirty: false,
+        })
+        .add_system(open_editor)
+        .add_system(editor_keys)
+        .add_system(editor_paint)
+        .add_system(editor_markers)
+        .add_system(editor_text);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tool {
+    Field,
+    Terminal,
+    Route,
+    Player,
+    Source,
+    Creature(Species),
+}
+
+impl Tool {
+    fn name(&self) -> &'static str {
+        match self {
+            Tool::Field => "field",
+            Tool::Terminal => "terminal",
+            Tool::Route => "route",
+            Tool::Player => "player",
+            Tool::Source => "walnut tree",
+            Tool::Creature(species) => species.name(),
+        }
+    }
+}
+
+pub struct Editor {
+    pub level: Level,
+    pub tool: Tool,
+    // 航路を編集している船
+    pub line: usize,
+    message: String,
+    // 描き直しが必要
+    dirty: bool,
+}
+
+impl Editor {
+    fn edited(&mut self, message: &str) {
+        self.message = message.to_string();
+        self.dirty = true;
+    }
+}
+
+/// エディタの画面に置いた目印。ゲームの Field や生き物とは別物
+#[derive(Component)]
+pub struct EditorMarker;
+
+#[derive(Component)]
+pub struct EditorText;
+
+fn open_editor(
+    mut commands: Commands,
+    mut screen: ResMut<Screen>,
+    mut editor: ResMut<Editor>,
+    stage: Res<Stage>,
+    key: Res<Input<KeyCode>>,
+    entity_query: Query<Entity>,
+    asset_server: Res<AssetServer>,
+) {
+    if *screen != Screen::Title || !key.just_pressed(KeyCode::E) {
+        return;
+    }
+    entity_query
+        .iter()
+        .for_each(|entity| commands.entity(entity).despawn());
+    spawn_cameras(&mut commands);
+    spawn_editor_text(&mut commands, &asset_server);
+
+    // いま選んでいる島を元に作り始める
+    editor.level = stage.level.clone();
+    editor.level.name = CUSTOM_LEVEL.to_string();
+    editor.line = 0;
+    editor.edited("");
+    *screen = Screen::Editor;
+}
+
+fn spawn_editor_text(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px((FIELD_LEFTBTM_X * UNIT_WIDTH as i32) as f32),
+                    top: Val::Px(UNIT_HEIGHT as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(EditorText);
+}
+
+fn editor_keys(
+    mut commands: Commands,
+    mut screen: ResMut<Screen>,
+    mut editor: ResMut<Editor>,
+    mut stage: ResMut<Stage>,
+    mut mode: ResMut<GameMode>,
+    key: Res<Input<KeyCode>>,
+    mut start_events: EventWriter<StartGame>,
+    entity_query: Query<Entity>,
+    asset_server: Res<AssetServer>,
+) {
+    if *screen != Screen::Editor {
+        return;
+    }
+    let tools = [
+        (KeyCode::F, Tool::Field),
+        (KeyCode::T, Tool::Terminal),
+        (KeyCode::V, Tool::Route),
+        (KeyCode::H, Tool::Player),
+        (KeyCode::W, Tool::Source),
+        (KeyCode::Key1, Tool::Creature(Species::Walnut)),
+        (KeyCode::Key2, Tool::Creature(Species::Fox)),
+        (KeyCode::Key3, Tool::Creature(Species::StrongBear)),
+        (KeyCode::Key4, Tool::Creature(Species::WeakBear)),
+    ];
+    tools.iter().for_each(|(code, tool)| {
+        if key.just_pressed(*code) {
+            editor.tool = *tool;
+        }
+    });
+    if key.just_pressed(KeyCode::Tab) && !editor.level.lines.is_empty() {
+        editor.line = (editor.line + 1) % editor.level.lines.len();
+        editor.edited("");
+    }
+
+    if key.just_pressed(KeyCode::S) {
+        storage::write(CUSTOM_LEVEL_FILE, &editor.level.to_text());
+        let message = match editor.level.validate() {
+            Ok(()) => format!("saved {}", CUSTOM_LEVEL_FILE),
+            Err(e) => format!("saved {} (not playable: {})", CUSTOM_LEVEL_FILE, e),
+        };
+        editor.edited(&message);
+    }
+    if key.just_pressed(KeyCode::L) {
+        match storage::read(CUSTOM_LEVEL_FILE).map(|text| Level::parse(&text)) {
+            Some(Ok(level)) => {
+                editor.level = level;
+                editor.line = 0;
+                editor.edited(&format!("loaded {}", CUSTOM_LEVEL_FILE));
+            }
+            Some(Err(e)) => editor.edited(&format!("failed to load: {}", e)),
+            None => editor.edited(&format!("{} not found", CUSTOM_LEVEL_FILE)),
+        }
+    }
+
+    if key.just_pressed(KeyCode::Return) {
+        match editor.level.validate() {
+            Ok(()) => {
+                stage.level = editor.level.clone();
+                *mode = GameMode::Normal;
+                start_events.send(StartGame);
+            }
+            Err(e) => editor.edited(&format!("not playable: {}", e)),
+        }
+    }
+    if key.just_pressed(KeyCode::Escape) {
+        entity_query
+            .iter()
+            .for_each(|entity| commands.entity(entity).despawn());
+        spawn_cameras(&mut commands);
+        spawn_title(&mut commands, &asset_server);
+        *screen = Screen::Title;
+    }
+}
+
+// 航路の今の終点
+fn route_end(line: &ShipLine) -> Position {
+    line.route
+        .iter()
+        .fold(line.terminal, |pos, step| &pos + step)
+}
+
+fn editor_paint(
+    screen: Res<Screen>,
+    mut editor: ResMut<Editor>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    camera_query: CameraQuery,
+) {
+    if *screen != Screen::Editor {
+        return;
+    }
+    let paint = mouse.just_pressed(MouseButton::Left);
+    let erase = mouse.just_pressed(MouseButton::Right);
+    if !paint && !erase {
+        return;
+    }
+    let grid = match cursor_grid(&windows, &camera_query) {
+        Some(grid) => grid,
+        None => return,
+    };
+    let editor = &mut *editor;
+    let level = &mut editor.level;
+
+    match editor.tool {
+        Tool::Field => {
+            level.fields.retain(|pos| *pos != grid);
+            if paint {
+                level.fields.push(grid);
+            }
+        }
+        Tool::Terminal => {
+            if erase {
+                level.lines.retain(|line| line.terminal != grid);
+            } else if level.is_field(&grid) {
+                level.lines.push(ShipLine {
+                    terminal: grid,
+                    route: vec![],
+                    first_arrival: SHIP_FIRST_ARRIVAL,
+                    interval: SHIP_INTERVAL,
+                    docking: SHIP_DOCKING,
+                });
+            } else {
+                editor.edited("terminal must be on the field");
+                return;
+            }
+            editor.line = level.lines.len().saturating_sub(1);
+        }
+        Tool::Route => {
+            let line = match level.lines.get_mut(editor.line) {
+                Some(line) => line,
+                None => {
+                    editor.edited("place a terminal first");
+                    return;
+                }
+            };
+            if erase {
+                line.route.pop();
+            } else {
+                // 航路は隣のマスへ 1 つずつ伸ばす
+                let end = route_end(line);
+                let step = Position {
+                    x: grid.x - end.x,
+                    y: grid.y - end.y,
+                    z: 0,
+                };
+                if step.x.abs() > 1 || step.y.abs() > 1 || step == Position::stay() {
+                    editor.edited("extend the route to a neighbouring tile");
+                    return;
+                }
+                line.route.push(step);
+            }
+        }
+        Tool::Player => {
+            if paint {
+                level.player = grid;
+            }
+        }
+        Tool::Source => {
+            level.walnut_sources.retain(|pos| *pos != grid);
+            if paint {
+                level.walnut_sources.push(grid);
+            }
+        }
+        Tool::Creature(species) => {
+            level.creatures.retain(|(_, pos)| *pos != grid);
+            if paint {
+                level.creatures.push((species, grid));
+            }
+        }
+    }
+    editor.edited("");
+}
+
+pub fn marker_color(species: Species) -> Color {
+    match species {
+        Species::Human => Color::PURPLE,
+        Species::StrongBear => Color::rgb_u8(148, 115, 91),
+        Species::WeakBear => Color::rgb_u8(148, 115, 255),
+        Species::Fox => Color::ORANGE,
+        Species::Walnut => Color::YELLOW,
+        Species::Squirrel => Color::rgb_u8(160, 82, 45),
+    }
+}
+
+fn spawn_tile_marker(commands: &mut Commands, position: Position, color: Color) {
+    let shape = shapes::Rectangle {
+        extents: Vec2::new(UNIT_WIDTH as f32, UNIT_HEIGHT as f32),
+        ..shapes::Rectangle::default()
+    };
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(color),
+                outline_mode: StrokeMode::new(Color::BLACK, 0.0),
+            },
+            Transform::default(),
+        ))
+        .insert(position)
+        .insert(EditorMarker);
+}
+
+fn spawn_dot_marker(commands: &mut Commands, position: Position, color: Color, radius: f32) {
+    let shape = shapes::Circle {
+        radius,
+        center: Vec2::new(0.0, 0.0),
+    };
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(color),
+                outline_mode: StrokeMode::new(Color::BLACK, 1.0),
+            },
+            Transform::default(),
+        ))
+        .insert(position)
+        .insert(EditorMarker);
+}
+
+// 変更があったら目印をすべて描き直す
+fn editor_markers(
+    mut commands: Commands,
+    screen: Res<Screen>,
+    mut editor: ResMut<Editor>,
+    marker_query: Query<Entity, With<EditorMarker>>,
+) {
+    if *screen != Screen::Editor || !editor.dirty {
+        return;
+    }
+    editor.dirty = false;
+    marker_query
+        .iter()
+        .for_each(|entity| commands.entity(entity).despawn());
+
+    let level = &editor.level;
+    let radius = (UNIT_WIDTH / 2) as f32;
+    level.fields.iter().for_each(|pos| {
+        spawn_tile_marker(
+            &mut commands,
+            Position {
+                z: FIELD_LAYER,
+                ..*pos
+            },
+            Color::GREEN,
+        );
+    });
+    level.lines.iter().enumerate().for_each(|(i, line)| {
+        spawn_tile_marker(
+            &mut commands,
+            Position {
+                z: TERMINAL_LAYER,
+                ..line.terminal
+            },
+            Color::rgb(0.7, 0.7, 1.0),
+        );
+        // 編集中の船の航路だけ目立たせる
+        let color = if i == editor.line {
+            Color::WHITE
+        } else {
+            Color::GRAY
+        };
+        line.route.iter().fold(line.terminal, |pos, step| {
+            let next = &pos + step;
+            spawn_dot_marker(
+                &mut commands,
+                Position {
+                    z: TERMINAL_LAYER,
+                    ..next
+                },
+                color,
+                radius / 3.0,
+            );
+            next
+        });
+    });
+    level.walnut_sources.iter().for_each(|pos| {
+        spawn_tile_marker(
+            &mut commands,
+            Position {
+                z: TERMINAL_LAYER,
+                ..*pos
+            },
+            Color::DARK_GREEN,
+        );
+    });
+    level.creatures.iter().for_each(|(species, pos)| {
+        spawn_dot_marker(
+            &mut commands,
+            Position {
+                z: PLAYER_LAYER,
+                ..*pos
+            },
+            marker_color(*species),
+            radius,
+        );
+    });
+    spawn_dot_marker(
+        &mut commands,
+        Position {
+            z: BEAR_LAYER,
+            ..level.player
+        },
+        marker_color(Species::Human),
+        radius,
+    );
+}
+
+fn editor_text(editor: Res<Editor>, mut text_query: Query<&mut Text, With<EditorText>>) {
+    let value = format!(
+        "Level editor  tool: {}  ship: {}/{}\nF:field T:terminal V:route H:player W:walnut tree 1-4:creatures Tab:next ship\nS:save L:load Enter:play Esc:title\n{}",
+        editor.tool.name(),
+        editor.line + 1,
+        editor.level.lines.len(),
+        editor.message
+    );
+    text_query.iter_mut().for_each(|mut text| {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    });
+}
@@ -0,0 +1,225 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::level::*;
+use crate::ship::ShipLine;
+
+use rand::prelude::*;
+use std::collections::HashSet;
+
+/*
+ * seed から島を作る
+ * 1. 粗さの違う 2 枚の value noise に、中心から離れるほど低くなる傾きを足して陸地を決める
+ * 2. 一番大きな陸地だけを残し、湖は埋める (どこへでも歩いて行けるように)
+ * 3. 外周に出られる海岸に Terminal を置き、船は外周を一周する
+ */
+pub fn generate(seed: u64) -> Level {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..ISLAND_ATTEMPTS {
+        let land = island_shape(&mut rng);
+        if land.len() >= ISLAND_MIN_FIELDS {
+            return build_level(land, &mut rng);
+        }
+    }
+    // どうしても小さい島しかできなければ、いつもの島にする
+    Level {
+        name: PROCEDURAL_LEVEL.to_string(),
+        ..Level::island()
+    }
+}
+
+/// 格子点にだけ乱数を置き、間は滑らかに補間する
+struct ValueNoise {
+    cell: i32,
+    cols: i32,
+    values: Vec<f32>,
+}
+
+impl ValueNoise {
+    fn new<R: Rng>(rng: &mut R, width: i32, height: i32, cell: i32) -> ValueNoise {
+        let cols = width / cell + 2;
+        let rows = height / cell + 2;
+        ValueNoise {
+            cell,
+            cols,
+            values: (0..cols * rows).map(|_| rng.gen_range(0.0..1.0)).collect(),
+        }
+    }
+    fn lattice(&self, x: i32, y: i32) -> f32 {
+        self.values[(y * self.cols + x) as usize]
+    }
+    fn at(&self, x: i32, y: i32) -> f32 {
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (cx, cy) = (x / self.cell, y / self.cell);
+        let fx = smooth(((x % self.cell) as f32 + 0.5) / self.cell as f32);
+        let fy = smooth(((y % self.cell) as f32 + 0.5) / self.cell as f32);
+        let bottom = self.lattice(cx, cy) * (1.0 - fx) + self.lattice(cx + 1, cy) * fx;
+        let top = self.lattice(cx, cy + 1) * (1.0 - fx) + self.lattice(cx + 1, cy + 1) * fx;
+        bottom * (1.0 - fy) + top * fy
+    }
+}
+
+// 船が回れるように、Field の範囲より 1 マス内側に島を作る
+fn island_bounds() -> (i32, i32, i32, i32) {
+    (
+        FIELD_LEFTBTM_X + 1,
+        FIELD_LEFTBTM_Y + 1,
+        FIELD_WIDTH as i32 - 2,
+        FIELD_HEIGHT as i32 - 2,
+    )
+}
+
+fn island_shape<R: Rng>(rng: &mut R) -> HashSet<(i32, i32)> {
+    let (left, bottom, width, height) = island_bounds();
+    let coarse = ValueNoise::new(rng, width, height, ISLAND_NOISE_CELL);
+    let fine = ValueNoise::new(rng, width, height, ISLAND_NOISE_CELL / 2);
+
+    let mut land = HashSet::new();
+    for x in 0..width {
+        for y in 0..height {
+            let dx = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let dy = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+            let falloff = (dx * dx + dy * dy).sqrt();
+            // 2 枚の平均が 0 になるようにずらす
+            let noise = coarse.at(x, y) + 0.5 * fine.at(x, y) - 0.75;
+            if 1.0 - falloff + noise * ISLAND_ROUGHNESS > ISLAND_SEA_LEVEL {
+                land.insert((left + x, bottom + y));
+            }
+        }
+    }
+    fill_lakes(&largest_region(&land))
+}
+
+const SIDES: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// 上下左右でつながっているマス
+fn flood(from: (i32, i32), area: &dyn Fn((i32, i32)) -> bool) -> HashSet<(i32, i32)> {
+    let mut region = HashSet::new();
+    let mut stack = vec![from];
+    while let Some((x, y)) = stack.pop() {
+        if !area((x, y)) || !region.insert((x, y)) {
+            continue;
+        }
+        SIDES
+            .iter()
+            .for_each(|(dx, dy)| stack.push((x + dx, y + dy)));
+    }
+    region
+}
+
+fn largest_region(land: &HashSet<(i32, i32)>) -> HashSet<(i32, i32)> {
+    let mut rest = land.clone();
+    let mut largest = HashSet::new();
+    // HashSet の順番に依らないよう、左下から順に調べる
+    let mut tiles: Vec<_> = land.iter().copied().collect();
+    tiles.sort_by_key(|&(x, y)| (y, x));
+    for tile in tiles {
+        if !rest.contains(&tile) {
+            continue;
+        }
+        let region = flood(tile, &|pos| land.contains(&pos));
+        region.iter().for_each(|pos| {
+            rest.remove(pos);
+        });
+        if region.len() > largest.len() {
+            largest = region;
+        }
+    }
+    largest
+}
+
+// 外の海とつながっていない海は陸にする
+fn fill_lakes(land: &HashSet<(i32, i32)>) -> HashSet<(i32, i32)> {
+    let (left, bottom, width, height) = island_bounds();
+    let inside = |(x, y): (i32, i32)| {
+        x >= left - 1 && x <= left + width && y >= bottom - 1 && y <= bottom + height
+    };
+    let sea = flood((left - 1, bottom - 1), &|pos| {
+        inside(pos) && !land.contains(&pos)
+    });
+    let mut filled = HashSet::new();
+    for x in left..left + width {
+        for y in bottom..bottom + height {
+            if !sea.contains(&(x, y)) {
+                filled.insert((x, y));
+            }
+        }
+    }
+    filled
+}
+
+fn build_level<R: Rng>(land: HashSet<(i32, i32)>, rng: &mut R) -> Level {
+    let mut fields: Vec<Position> = land.iter().map(|&(x, y)| grid(x, y)).collect();
+    fields.sort_by_key(|pos| (pos.y, pos.x));
+
+    // 外周の隣にある海岸ならどこでも Terminal にできる
+    let contour = coastline(&fields);
+    let coast: Vec<Position> = fields
+        .iter()
+        .copied()
+        .filter(|pos| {
+            contour
+                .iter()
+                .any(|sea| (sea.x - pos.x).abs() <= 1 && (sea.y - pos.y).abs() <= 1)
+        })
+        .collect();
+    let terminal = *coast.choose(rng).unwrap_or(&fields[0]);
+    // Player は Terminal から一番遠いところから始める
+    let player = *fields
+        .iter()
+        .max_by_key(|pos| (pos.x - terminal.x).abs() + (pos.y - terminal.y).abs())
+        .unwrap_or(&terminal);
+    let route = coastline_route(&fields, terminal);
+
+    Level {
+        name: PROCEDURAL_LEVEL.to_string(),
+        fields,
+        player,
+        creatures: vec![],
+        random: vec![
+            (Species::StrongBear, INITIAL_BEAR_NUM),
+            (Species::Fox, INITIAL_FOX_NUM),
+            (Species::Walnut, INITIAL_WALNUT_NUM),
+        ],
+        walnut_sources: vec![],
+        walnut_rate: PROBABILITY_INCREASE_WALNUT,
+        lines: vec![ShipLine {
+            terminal,
+            route,
+            first_arrival: SHIP_FIRST_ARRIVAL,
+            interval: SHIP_INTERVAL,
+            docking: SHIP_DOCKING,
+        }],
+        objectives: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn islands_are_one_playable_piece() {
+        (0..20).for_each(|seed| {
+            let level = generate(seed);
+            assert_eq!(level.validate(), Ok(()), "seed {}", seed);
+
+            let land: HashSet<(i32, i32)> = level.fields.iter().map(|pos| (pos.x, pos.y)).collect();
+            assert!(land.len() >= ISLAND_MIN_FIELDS, "seed {}", seed);
+            let first = (level.fields[0].x, level.fields[0].y);
+            let region = flood(first, &|pos| land.contains(&pos));
+            assert_eq!(region.len(), land.len(), "seed {}", seed);
+
+            // Terminal は外周の海に面している
+            let terminal = level.lines[0].terminal;
+            assert!(
+                coastline(&level.fields)
+                    .iter()
+                    .any(|sea| (sea.x - terminal.x).abs() <= 1 && (sea.y - terminal.y).abs() <= 1),
+                "seed {}",
+                seed
+            );
+
+            assert_eq!(generate(seed).to_text(), level.to_text(), "seed {}", seed);
+        });
+    }
+}
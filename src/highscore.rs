@@ -0,0 +1,274 @@
+use crate::camera::spawn_cameras;
+use crate::consts::*;
+use crate::difficulty::Difficulty;
+use crate::score::*;
+use crate::storage;
+use crate::title::*;
+use crate::Stage;
+
+use bevy::prelude::*;
+
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Leaderboard::load())
+            .insert_resource(Initials(String::new()))
+            .add_system(begin_name_entry)
+            .add_system(name_entry.label("name_entry"));
+    }
+}
+
+pub struct Entry {
+    pub level: String,
+    pub seed: u64,
+    pub initials: String,
+    pub score: i32,
+    pub difficulty: Difficulty,
+}
+
+impl Entry {
+    // 保存したファイルを書き換えられても分かるように、各行に checksum を付ける
+    // 難しさを記録する前の行 (Normal) は、難しさを含めずに計算する
+    fn checksum(&self) -> u64 {
+        let mut value = format!(
+            "{}|{}|{}|{}|{}",
+            HIGHSCORE_SALT, self.level, self.seed, self.initials, self.score
+        );
+        if self.difficulty != Difficulty::Normal {
+            value.push_str(&format!("|{}", self.difficulty.name()));
+        }
+        value.bytes().fold(0xcbf29ce484222325, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{:016x}",
+            self.level,
+            self.seed,
+            self.initials,
+            self.score,
+            self.difficulty.name(),
+            self.checksum()
+        )
+    }
+    fn from_line(line: &str) -> Option<Entry> {
+        let cols: Vec<&str> = line.split('\t').collect();
+        let difficulty = match cols.len() {
+            5 => Difficulty::Normal,
+            6 => Difficulty::from_name(cols[4])?,
+            _ => return None,
+        };
+        let entry = Entry {
+            level: cols[0].to_string(),
+            seed: cols[1].parse().ok()?,
+            initials: cols[2].to_string(),
+            score: cols[3].parse().ok()?,
+            difficulty,
+        };
+        if u64::from_str_radix(cols[cols.len() - 1], 16).ok()? != entry.checksum() {
+            warn!("discard tampered high score: {}", line);
+            return None;
+        }
+        Some(entry)
+    }
+}
+
+/// level と seed と難しさごとの上位 HIGHSCORE_NUM 件
+pub struct Leaderboard {
+    pub entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    pub fn load() -> Leaderboard {
+        Leaderboard {
+            entries: storage::read(HIGHSCORE_FILE)
+                .unwrap_or_default()
+                .lines()
+                .filter_map(Entry::from_line)
+                .collect(),
+        }
+    }
+    pub fn save(&self) {
+        let value = self
+            .entries
+            .iter()
+            .map(|entry| entry.to_line())
+            .collect::<Vec<_>>()
+            .join("\n");
+        storage::write(HIGHSCORE_FILE, &value);
+    }
+    pub fn top(&self, level: &str, seed: u64, difficulty: Difficulty) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry.level == level && entry.seed == seed && entry.difficulty == difficulty
+            })
+            .collect();
+        entries.sort_by_key(|entry| -entry.score);
+        entries.truncate(HIGHSCORE_NUM);
+        entries
+    }
+    pub fn insert(&mut self, entry: Entry) {
+        let (level, seed, difficulty) = (entry.level.clone(), entry.seed, entry.difficulty);
+        self.entries.push(entry);
+        self.entries.sort_by_key(|entry| -entry.score);
+
+        let mut rank = 0;
+        self.entries.retain(|entry| {
+            if entry.level != level || entry.seed != seed || entry.difficulty != difficulty {
+                return true;
+            }
+            rank += 1;
+            rank <= HIGHSCORE_NUM
+        });
+    }
+}
+
+/// 入力中のイニシャル
+pub struct Initials(pub String);
+
+#[derive(Component)]
+pub struct InitialsText;
+
+fn begin_name_entry(
+    mut commands: Commands,
+    score: Option<Res<Score>>,
+    mut screen: ResMut<Screen>,
+    mut initials: ResMut<Initials>,
+    asset_server: Res<AssetServer>,
+) {
+    if *screen != Screen::Playing || score.is_none() {
+        return;
+    }
+    *screen = Screen::NameEntry;
+    initials.0.clear();
+
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::YELLOW,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px((2 * UNIT_WIDTH) as f32),
+                    bottom: Val::Px((9 * UNIT_HEIGHT) as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(InitialsText);
+}
+
+fn name_entry(
+    mut commands: Commands,
+    mut screen: ResMut<Screen>,
+    mut initials: ResMut<Initials>,
+    mut leaderboard: ResMut<Leaderboard>,
+    stage: Res<Stage>,
+    difficulty: Res<Difficulty>,
+    score: Option<Res<Score>>,
+    key: Res<Input<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut text_query: Query<&mut Text, With<InitialsText>>,
+    entity_query: Query<Entity>,
+    asset_server: Res<AssetServer>,
+) {
+    if *screen != Screen::NameEntry {
+        chars.iter().for_each(drop);
+        return;
+    }
+
+    chars.iter().for_each(|c| {
+        if c.char.is_ascii_alphabetic() && initials.0.len() < 3 {
+            initials.0.push(c.char.to_ascii_uppercase());
+        }
+    });
+    if key.just_pressed(KeyCode::Back) {
+        initials.0.pop();
+    }
+
+    text_query.iter_mut().for_each(|mut text| {
+        text.sections[0].value = format!("Your initials: {:_<3}\nPress Enter", initials.0);
+    });
+
+    if !key.just_pressed(KeyCode::Return) || initials.0.is_empty() {
+        return;
+    }
+    if let Some(score) = score {
+        leaderboard.insert(Entry {
+            level: stage.level.name.clone(),
+            seed: stage.seed,
+            initials: initials.0.clone(),
+            score: score.total(),
+            difficulty: *difficulty,
+        });
+        leaderboard.save();
+    }
+
+    // タイトルに戻ってランキングを見せる
+    entity_query
+        .iter()
+        .for_each(|entity| commands.entity(entity).despawn());
+    commands.remove_resource::<Score>();
+    spawn_cameras(&mut commands);
+    spawn_title(&mut commands, &asset_server);
+    *screen = Screen::Title;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(difficulty: Difficulty) -> Entry {
+        Entry {
+            level: "island".to_string(),
+            seed: 42,
+            initials: "ABC".to_string(),
+            score: 1234,
+            difficulty,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_with_checksum() {
+        Difficulty::ALL.iter().for_each(|difficulty| {
+            let line = entry(*difficulty).to_line();
+            let parsed = Entry::from_line(&line).unwrap();
+            assert_eq!(parsed.level, "island");
+            assert_eq!(parsed.seed, 42);
+            assert_eq!(parsed.initials, "ABC");
+            assert_eq!(parsed.score, 1234);
+            assert_eq!(parsed.difficulty, *difficulty);
+        });
+    }
+
+    #[test]
+    fn tampered_lines_are_discarded() {
+        let line = entry(Difficulty::Hard).to_line();
+        assert!(Entry::from_line(&line.replace("1234", "9999")).is_none());
+        assert!(Entry::from_line(&line.replace("hard", "easy")).is_none());
+        assert!(Entry::from_line(&line.replace("ABC", "XYZ")).is_none());
+        assert!(Entry::from_line("island\t42\tABC").is_none());
+    }
+
+    // 難しさを記録する前の 5 列の行は Normal として読む
+    #[test]
+    fn legacy_lines_are_normal() {
+        let line = "island\t42\tABC\t1234\t26bcee6511340b89";
+        let parsed = Entry::from_line(line).unwrap();
+        assert_eq!(parsed.difficulty, Difficulty::Normal);
+        assert_eq!(parsed.score, 1234);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::bag::Bag;
+use crate::components::*;
+use crate::consts::*;
+use crate::craft::Tools;
+use crate::Stamina;
+
+use bevy::prelude::*;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(hud_bar).add_system(hud_text);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum HudKind {
+    Hp,
+    Satiety,
+    Stamina,
+    // 袋の中身と、拾った材料。文字だけでバーは無い
+    Bag,
+    Tools,
+}
+
+const HUD_KINDS: [HudKind; 5] = [
+    HudKind::Hp,
+    HudKind::Satiety,
+    HudKind::Stamina,
+    HudKind::Bag,
+    HudKind::Tools,
+];
+
+#[derive(Component)]
+pub struct HudBar {
+    kind: HudKind,
+    player: usize,
+}
+
+#[derive(Component)]
+pub struct HudText {
+    kind: HudKind,
+    player: usize,
+    // 2 人のときは "P1 " のように誰のものかを書く
+    prefix: String,
+}
+
+// 画面右の余白 (Field の外) に並べる
+pub fn hud_left() -> f32 {
+    ((FIELD_LEFTBTM_X + FIELD_WIDTH as i32 + 1) * UNIT_WIDTH as i32) as f32
+}
+
+// HUD の下端。船の時刻はその下に書く
+pub fn hud_bottom(players: usize) -> f32 {
+    HUD_TOP + (players.max(1) * HUD_KINDS.len()) as f32 * HUD_ROW_HEIGHT
+}
+
+// Player ごとに縦に並べる
+pub fn spawn_hud(commands: &mut Commands, asset_server: &Res<AssetServer>, players: usize) {
+    let kinds = HUD_KINDS;
+    (0..players.max(1)).for_each(|player| {
+        let prefix = if players > 1 {
+            format!("P{} ", player + 1)
+        } else {
+            String::new()
+        };
+        kinds.iter().enumerate().for_each(|(i, kind)| {
+            let row = player * kinds.len() + i;
+            let top = HUD_TOP + row as f32 * HUD_ROW_HEIGHT;
+            let hud_text = HudText {
+                kind: *kind,
+                player,
+                prefix: prefix.clone(),
+            };
+            spawn_hud_text(commands, hud_text, top, asset_server);
+            let color = match kind {
+                HudKind::Hp => Color::RED,
+                HudKind::Satiety => Color::ORANGE,
+                HudKind::Stamina => Color::CYAN,
+                HudKind::Bag | HudKind::Tools => return,
+            };
+            spawn_hud_bar(commands, *kind, color, player, top + HUD_FONT_SIZE + 4.0);
+        });
+    });
+}
+
+fn spawn_hud_text(
+    commands: &mut Commands,
+    hud_text: HudText,
+    top: f32,
+    asset_server: &Res<AssetServer>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(hud_left()),
+                    top: Val::Px(top),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(hud_text);
+}
+
+fn spawn_hud_bar(commands: &mut Commands, kind: HudKind, color: Color, player: usize, top: f32) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(HUD_BAR_WIDTH), Val::Px(HUD_BAR_HEIGHT)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(hud_left()),
+                    top: Val::Px(top),
+                    ..default()
+                },
+                ..default()
+            },
+            color: UiColor(Color::DARK_GRAY),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    color: UiColor(color),
+                    ..Default::default()
+                })
+                .insert(HudBar { kind, player });
+        });
+}
+
+// 0.0 ~ 1.0
+fn ratio(kind: HudKind, hp: &HP, satiety: &Satiety, stamina: &Stamina) -> f32 {
+    let r = match kind {
+        HudKind::Hp => hp.val / hp.max,
+        // 人間の満腹度は上限が無いので HUD_MAX_SATIETY で目盛る
+        HudKind::Satiety => satiety.val / f32::min(satiety.max, HUD_MAX_SATIETY),
+        HudKind::Stamina => stamina.val as f32 / MAX_STAMINA as f32,
+        HudKind::Bag | HudKind::Tools => 0.0,
+    };
+    r.clamp(0.0, 1.0)
+}
+
+fn hud_bar(
+    player_query: Query<(&Player, &HP, &Satiety, &Stamina)>,
+    mut bar_query: Query<(&HudBar, &mut Style)>,
+) {
+    player_query
+        .iter()
+        .for_each(|(player, hp, satiety, stamina)| {
+            bar_query
+                .iter_mut()
+                .filter(|(bar, _)| bar.player == player.id)
+                .for_each(|(bar, mut style)| {
+                    style.size.width = Val::Percent(ratio(bar.kind, hp, satiety, stamina) * 100.0);
+                });
+        });
+}
+
+fn hud_text(
+    player_query: Query<(
+        &Player,
+        &HP,
+        &Satiety,
+        &Stamina,
+        Option<&Bag>,
+        Option<&Tools>,
+    )>,
+    mut text_query: Query<(&HudText, &mut Text)>,
+) {
+    player_query
+        .iter()
+        .for_each(|(player, hp, satiety, stamina, bag, tools)| {
+            text_query
+                .iter_mut()
+                .filter(|(hud_text, _)| hud_text.player == player.id)
+                .for_each(|(hud_text, mut text)| {
+                    let value = match hud_text.kind {
+                        HudKind::Hp => format!("HP {}/{}", hp.val.max(0.0) as i32, hp.max as i32),
+                        HudKind::Satiety => format!("Satiety {}", satiety.val as i32),
+                        HudKind::Stamina => {
+                            if stamina.can_move() {
+                                "Stamina OK".to_string()
+                            } else {
+                                "Stamina".to_string()
+                            }
+                        }
+                        HudKind::Bag => bag_text(bag),
+                        HudKind::Tools => tools_text(tools),
+                    };
+                    text.sections[0].value = format!("{}{}", hud_text.prefix, value);
+                });
+        });
+}
+
+// 例: "Bag walnut, fox (carry)"
+fn bag_text(bag: Option<&Bag>) -> String {
+    let bag = match bag {
+        Some(bag) => bag,
+        None => return String::new(),
+    };
+    let items = if bag.items.is_empty() {
+        "-".to_string()
+    } else {
+        bag.items
+            .iter()
+            .map(|species| species.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let carrying = if bag.carrying { " (carry)" } else { "" };
+    format!("Bag {}{}", items, carrying)
+}
+
+// 例: "Stick 2 Stone 1 > trap | spear 2"。人間でなければ何も書かない
+fn tools_text(tools: Option<&Tools>) -> String {
+    let tools = match tools {
+        Some(tools) => tools,
+        None => return String::new(),
+    };
+    let mut value = format!(
+        "Stick {} Stone {} > {}",
+        tools.sticks,
+        tools.stones,
+        tools.recipe.name()
+    );
+    if tools.spear > 0 {
+        value.push_str(&format!(" | spear {}", tools.spear));
+    }
+    if tools.raft {
+        value.push_str(" | raft");
+    }
+    value
+}
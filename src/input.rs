@@ -0,0 +1,668 @@
+use crate::consts::*;
+use crate::storage;
+use crate::title::Screen;
+use crate::GameMode;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub struct ActionPlugin;
+
+/*
+ * キーボードとゲームパッドの入力を Action にまとめる
+ * 割り当ては BINDINGS_FILE に 1 行ずつ書く。無ければ初期設定を書き出す
+ *   <action> key <KeyCode>
+ *   <action> pad <GamepadButtonType>
+ * ファイルに書いた Action は、その行の割り当てだけになる
+ * 2 人で遊ぶときは p2_* が 2P の操作。ゲームパッドは繋いだ順に 1P, 2P
+ */
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bindings::load())
+            .insert_resource(Actions::default())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_actions.label("actions").after(InputSystem),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    Restart,
+    Pause,
+    Step,
+    SpeedUp,
+    SlowDown,
+    Interact,
+    ZoomIn,
+    ZoomOut,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    Follow,
+    P2MoveUp,
+    P2MoveDown,
+    P2MoveLeft,
+    P2MoveRight,
+    P2MoveUpLeft,
+    P2MoveUpRight,
+    P2MoveDownLeft,
+    P2MoveDownRight,
+    Carry,
+    EatCarried,
+    P2Carry,
+    P2EatCarried,
+    Recipe,
+    Craft,
+    P2Recipe,
+    P2Craft,
+    LabelView,
+}
+
+impl Action {
+    pub const ALL: [Action; 38] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUpLeft,
+        Action::MoveUpRight,
+        Action::MoveDownLeft,
+        Action::MoveDownRight,
+        Action::Restart,
+        Action::Pause,
+        Action::Step,
+        Action::SpeedUp,
+        Action::SlowDown,
+        Action::Interact,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::PanUp,
+        Action::PanDown,
+        Action::PanLeft,
+        Action::PanRight,
+        Action::Follow,
+        Action::P2MoveUp,
+        Action::P2MoveDown,
+        Action::P2MoveLeft,
+        Action::P2MoveRight,
+        Action::P2MoveUpLeft,
+        Action::P2MoveUpRight,
+        Action::P2MoveDownLeft,
+        Action::P2MoveDownRight,
+        Action::Carry,
+        Action::EatCarried,
+        Action::P2Carry,
+        Action::P2EatCarried,
+        Action::Recipe,
+        Action::Craft,
+        Action::P2Recipe,
+        Action::P2Craft,
+        Action::LabelView,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUpLeft => "move_up_left",
+            Action::MoveUpRight => "move_up_right",
+            Action::MoveDownLeft => "move_down_left",
+            Action::MoveDownRight => "move_down_right",
+            Action::Restart => "restart",
+            Action::Pause => "pause",
+            Action::Step => "step",
+            Action::SpeedUp => "speed_up",
+            Action::SlowDown => "slow_down",
+            Action::Interact => "interact",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::PanUp => "pan_up",
+            Action::PanDown => "pan_down",
+            Action::PanLeft => "pan_left",
+            Action::PanRight => "pan_right",
+            Action::Follow => "follow",
+            Action::P2MoveUp => "p2_move_up",
+            Action::P2MoveDown => "p2_move_down",
+            Action::P2MoveLeft => "p2_move_left",
+            Action::P2MoveRight => "p2_move_right",
+            Action::P2MoveUpLeft => "p2_move_up_left",
+            Action::P2MoveUpRight => "p2_move_up_right",
+            Action::P2MoveDownLeft => "p2_move_down_left",
+            Action::P2MoveDownRight => "p2_move_down_right",
+            Action::Carry => "carry",
+            Action::EatCarried => "eat_carried",
+            Action::P2Carry => "p2_carry",
+            Action::P2EatCarried => "p2_eat_carried",
+            Action::Recipe => "recipe",
+            Action::Craft => "craft",
+            Action::P2Recipe => "p2_recipe",
+            Action::P2Craft => "p2_craft",
+            Action::LabelView => "label_view",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+    // 向き (x, y)
+    fn direction(&self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveUp | Action::P2MoveUp => Some((0, 1)),
+            Action::MoveDown | Action::P2MoveDown => Some((0, -1)),
+            Action::MoveLeft | Action::P2MoveLeft => Some((-1, 0)),
+            Action::MoveRight | Action::P2MoveRight => Some((1, 0)),
+            Action::MoveUpLeft | Action::P2MoveUpLeft => Some((-1, 1)),
+            Action::MoveUpRight | Action::P2MoveUpRight => Some((1, 1)),
+            Action::MoveDownLeft | Action::P2MoveDownLeft => Some((-1, -1)),
+            Action::MoveDownRight | Action::P2MoveDownRight => Some((1, -1)),
+            _ => None,
+        }
+    }
+    // どの Player の操作か
+    fn player(&self) -> usize {
+        match self {
+            Action::P2MoveUp
+            | Action::P2MoveDown
+            | Action::P2MoveLeft
+            | Action::P2MoveRight
+            | Action::P2MoveUpLeft
+            | Action::P2MoveUpRight
+            | Action::P2MoveDownLeft
+            | Action::P2MoveDownRight
+            | Action::P2Carry
+            | Action::P2EatCarried
+            | Action::P2Recipe
+            | Action::P2Craft => 1,
+            _ => 0,
+        }
+    }
+    /// 1P の操作を player のものにする。2 つ目のゲームパッドは 2P の操作になる
+    pub fn for_player(&self, player: usize) -> Action {
+        if player == 0 {
+            return *self;
+        }
+        match self {
+            Action::MoveUp => Action::P2MoveUp,
+            Action::MoveDown => Action::P2MoveDown,
+            Action::MoveLeft => Action::P2MoveLeft,
+            Action::MoveRight => Action::P2MoveRight,
+            Action::MoveUpLeft => Action::P2MoveUpLeft,
+            Action::MoveUpRight => Action::P2MoveUpRight,
+            Action::MoveDownLeft => Action::P2MoveDownLeft,
+            Action::MoveDownRight => Action::P2MoveDownRight,
+            Action::Carry => Action::P2Carry,
+            Action::EatCarried => Action::P2EatCarried,
+            Action::Recipe => Action::P2Recipe,
+            Action::Craft => Action::P2Craft,
+            _ => *self,
+        }
+    }
+}
+
+// 設定ファイルに書ける名前
+const KEY_NAMES: [(&str, KeyCode); 62] = [
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("Key0", KeyCode::Key0),
+    ("Key1", KeyCode::Key1),
+    ("Key2", KeyCode::Key2),
+    ("Key3", KeyCode::Key3),
+    ("Key4", KeyCode::Key4),
+    ("Key5", KeyCode::Key5),
+    ("Key6", KeyCode::Key6),
+    ("Key7", KeyCode::Key7),
+    ("Key8", KeyCode::Key8),
+    ("Key9", KeyCode::Key9),
+    ("Numpad0", KeyCode::Numpad0),
+    ("Numpad1", KeyCode::Numpad1),
+    ("Numpad2", KeyCode::Numpad2),
+    ("Numpad3", KeyCode::Numpad3),
+    ("Numpad4", KeyCode::Numpad4),
+    ("Numpad5", KeyCode::Numpad5),
+    ("Numpad6", KeyCode::Numpad6),
+    ("Numpad7", KeyCode::Numpad7),
+    ("Numpad8", KeyCode::Numpad8),
+    ("Numpad9", KeyCode::Numpad9),
+    ("NumpadAdd", KeyCode::NumpadAdd),
+    ("NumpadSubtract", KeyCode::NumpadSubtract),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Space", KeyCode::Space),
+    ("Return", KeyCode::Return),
+    ("Tab", KeyCode::Tab),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Home", KeyCode::Home),
+    ("Equals", KeyCode::Equals),
+    ("Minus", KeyCode::Minus),
+    ("Comma", KeyCode::Comma),
+    ("Period", KeyCode::Period),
+];
+
+const BUTTON_NAMES: [(&str, GamepadButtonType); 16] = [
+    ("South", GamepadButtonType::South),
+    ("East", GamepadButtonType::East),
+    ("North", GamepadButtonType::North),
+    ("West", GamepadButtonType::West),
+    ("LeftTrigger", GamepadButtonType::LeftTrigger),
+    ("RightTrigger", GamepadButtonType::RightTrigger),
+    ("LeftTrigger2", GamepadButtonType::LeftTrigger2),
+    ("RightTrigger2", GamepadButtonType::RightTrigger2),
+    ("Select", GamepadButtonType::Select),
+    ("Start", GamepadButtonType::Start),
+    ("DPadUp", GamepadButtonType::DPadUp),
+    ("DPadDown", GamepadButtonType::DPadDown),
+    ("DPadLeft", GamepadButtonType::DPadLeft),
+    ("DPadRight", GamepadButtonType::DPadRight),
+    ("LeftThumb", GamepadButtonType::LeftThumb),
+    ("RightThumb", GamepadButtonType::RightThumb),
+];
+
+fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, k)| *k == key)
+        .map(|(name, _)| *name)
+}
+
+fn button_name(button: GamepadButtonType) -> Option<&'static str> {
+    BUTTON_NAMES
+        .iter()
+        .find(|(_, b)| *b == button)
+        .map(|(name, _)| *name)
+}
+
+/// Action ごとのキーとボタン
+pub struct Bindings {
+    keys: HashMap<Action, Vec<KeyCode>>,
+    buttons: HashMap<Action, Vec<GamepadButtonType>>,
+    /// 先行入力を覚えておく数。0 なら覚えない
+    pub buffer: usize,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use GamepadButtonType as Pad;
+        use KeyCode as Key;
+        let keys = [
+            (Action::MoveUp, vec![Key::Up, Key::W]),
+            (Action::MoveDown, vec![Key::Down, Key::S]),
+            (Action::MoveLeft, vec![Key::Left, Key::A]),
+            (Action::MoveRight, vec![Key::Right, Key::D]),
+            (Action::MoveUpLeft, vec![Key::Q]),
+            (Action::MoveUpRight, vec![Key::E]),
+            (Action::MoveDownLeft, vec![Key::Z]),
+            (Action::MoveDownRight, vec![Key::C]),
+            (Action::Restart, vec![Key::R]),
+            (Action::Pause, vec![Key::P]),
+            (Action::Step, vec![Key::N]),
+            (Action::SpeedUp, vec![Key::Equals, Key::NumpadAdd]),
+            (Action::SlowDown, vec![Key::Minus, Key::NumpadSubtract]),
+            (Action::Interact, vec![Key::Return, Key::Space]),
+            (Action::ZoomIn, vec![Key::PageUp]),
+            (Action::ZoomOut, vec![Key::PageDown]),
+            (Action::PanUp, vec![Key::Numpad8]),
+            (Action::PanDown, vec![Key::Numpad2]),
+            (Action::PanLeft, vec![Key::Numpad4]),
+            (Action::PanRight, vec![Key::Numpad6]),
+            (Action::Follow, vec![Key::F, Key::Home]),
+            (Action::P2MoveUp, vec![Key::I]),
+            (Action::P2MoveDown, vec![Key::K]),
+            (Action::P2MoveLeft, vec![Key::J]),
+            (Action::P2MoveRight, vec![Key::L]),
+            (Action::P2MoveUpLeft, vec![Key::U]),
+            (Action::P2MoveUpRight, vec![Key::O]),
+            (Action::P2MoveDownLeft, vec![Key::M]),
+            (Action::P2MoveDownRight, vec![Key::Period]),
+            (Action::Carry, vec![Key::G]),
+            (Action::EatCarried, vec![Key::X]),
+            (Action::P2Carry, vec![Key::Y]),
+            (Action::P2EatCarried, vec![Key::Comma]),
+            (Action::Recipe, vec![Key::T]),
+            (Action::Craft, vec![Key::V]),
+            (Action::P2Recipe, vec![Key::B]),
+            (Action::P2Craft, vec![Key::H]),
+            (Action::LabelView, vec![Key::Tab]),
+        ];
+        let buttons = [
+            (Action::MoveUp, vec![Pad::DPadUp]),
+            (Action::MoveDown, vec![Pad::DPadDown]),
+            (Action::MoveLeft, vec![Pad::DPadLeft]),
+            (Action::MoveRight, vec![Pad::DPadRight]),
+            (Action::Restart, vec![Pad::Select]),
+            (Action::Pause, vec![Pad::Start]),
+            (Action::Step, vec![Pad::West]),
+            (Action::SpeedUp, vec![Pad::RightTrigger2]),
+            (Action::SlowDown, vec![Pad::LeftTrigger2]),
+            (Action::Interact, vec![Pad::South]),
+            (Action::ZoomIn, vec![Pad::RightTrigger]),
+            (Action::ZoomOut, vec![Pad::LeftTrigger]),
+            (Action::Follow, vec![Pad::North]),
+            (Action::Carry, vec![Pad::LeftThumb]),
+            (Action::EatCarried, vec![Pad::East]),
+            (Action::Craft, vec![Pad::RightThumb]),
+        ];
+        Bindings {
+            keys: keys.into_iter().collect(),
+            buttons: buttons.into_iter().collect(),
+            buffer: INPUT_BUFFER_LEN,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load() -> Bindings {
+        let mut bindings = Bindings::default();
+        let text = match storage::read(BINDINGS_FILE) {
+            Some(text) => text,
+            None => {
+                // 書き換えやすいように初期設定を置いておく
+                storage::write(BINDINGS_FILE, &bindings.to_text());
+                return bindings;
+            }
+        };
+
+        let mut overridden = HashSet::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.get(0) == Some(&"buffer") {
+                match cols.get(1).and_then(|n| n.parse().ok()) {
+                    Some(len) => bindings.buffer = len,
+                    None => warn!("broken line in {}: {}", BINDINGS_FILE, line),
+                }
+                continue;
+            }
+            let action = match cols.get(0).and_then(|name| Action::from_name(name)) {
+                Some(action) => action,
+                None => {
+                    warn!("unknown action in {}: {}", BINDINGS_FILE, line);
+                    continue;
+                }
+            };
+            if overridden.insert(action) {
+                bindings.keys.remove(&action);
+                bindings.buttons.remove(&action);
+            }
+            let name = cols.get(2).copied().unwrap_or("");
+            match cols.get(1).copied() {
+                Some("key") => match KEY_NAMES.iter().find(|(n, _)| *n == name) {
+                    Some((_, key)) => bindings.keys.entry(action).or_default().push(*key),
+                    None => warn!("unknown key in {}: {}", BINDINGS_FILE, line),
+                },
+                Some("pad") => match BUTTON_NAMES.iter().find(|(n, _)| *n == name) {
+                    Some((_, button)) => bindings.buttons.entry(action).or_default().push(*button),
+                    None => warn!("unknown button in {}: {}", BINDINGS_FILE, line),
+                },
+                _ => warn!("broken line in {}: {}", BINDINGS_FILE, line),
+            }
+        }
+        bindings
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![];
+        Action::ALL.iter().for_each(|action| {
+            self.keys.get(action).into_iter().flatten().for_each(|key| {
+                if let Some(name) = key_name(*key) {
+                    lines.push(format!("{} key {}", action.name(), name));
+                }
+            });
+            self.buttons
+                .get(action)
+                .into_iter()
+                .flatten()
+                .for_each(|button| {
+                    if let Some(name) = button_name(*button) {
+                        lines.push(format!("{} pad {}", action.name(), name));
+                    }
+                });
+        });
+        lines.push(format!("buffer {}", self.buffer));
+        lines.join("\n") + "\n"
+    }
+}
+
+/// このフレームの Action
+#[derive(Default)]
+pub struct Actions {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    // Player ごとの左スティック (移動) と、右スティック (カメラ)
+    pub move_axis: [Vec2; MAX_PLAYERS],
+    pub pan_axis: Vec2,
+    // 動けるようになるまでに押した移動の向き (古い順)
+    buffer: [VecDeque<(i32, i32)>; MAX_PLAYERS],
+    stick: [(i32, i32); MAX_PLAYERS],
+}
+
+impl Actions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+    /// 画面上のボタンなど、キー以外で押したとき
+    pub fn press(&mut self, action: Action) {
+        self.pressed.insert(action);
+    }
+    /// キー以外で押した瞬間。移動なら先行入力にも入れる
+    pub fn tap(&mut self, action: Action, buffer: usize) {
+        self.press(action);
+        self.just_pressed.insert(action);
+        if let Some(direction) = action.direction() {
+            self.push_buffer(action.player(), direction, buffer);
+        }
+    }
+    fn push_buffer(&mut self, player: usize, direction: (i32, i32), len: usize) {
+        if len == 0 || direction == (0, 0) {
+            return;
+        }
+        let buffer = &mut self.buffer[player];
+        if buffer.len() >= len {
+            buffer.pop_front();
+        }
+        buffer.push_back(direction);
+    }
+    /// player が押している移動の Action とスティックを合わせた向き
+    pub fn move_direction(&self, player: usize) -> (i32, i32) {
+        let (x, y) = self
+            .pressed
+            .iter()
+            .filter(|action| action.player() == player)
+            .filter_map(|action| action.direction())
+            .fold(self.stick_direction(player), |(x, y), (dx, dy)| {
+                (x + dx, y + dy)
+            });
+        (x.clamp(-1, 1), y.clamp(-1, 1))
+    }
+    fn stick_direction(&self, player: usize) -> (i32, i32) {
+        let tilt = |v: f32| {
+            if v.abs() >= GAMEPAD_DEADZONE {
+                v.signum() as i32
+            } else {
+                0
+            }
+        };
+        let axis = self.move_axis[player];
+        (tilt(axis.x), tilt(axis.y))
+    }
+    /// 先行入力があればその向き、なければ今押している向き
+    pub fn take_move(&mut self, player: usize) -> (i32, i32) {
+        self.buffer[player]
+            .pop_front()
+            .unwrap_or_else(|| self.move_direction(player))
+    }
+    pub fn clear_buffer(&mut self) {
+        self.buffer.iter_mut().for_each(|buffer| buffer.clear());
+    }
+}
+
+fn update_actions(
+    screen: Res<Screen>,
+    mode: Res<GameMode>,
+    bindings: Res<Bindings>,
+    key: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    button: Res<Input<GamepadButton>>,
+    axis: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<Actions>,
+) {
+    let actions = &mut *actions;
+    actions.pressed.clear();
+    actions.just_pressed.clear();
+    actions.move_axis = [Vec2::ZERO; MAX_PLAYERS];
+    actions.pan_axis = Vec2::ZERO;
+
+    // イニシャル入力中のキーは文字として扱う
+    if *screen != Screen::NameEntry {
+        bindings.keys.iter().for_each(|(action, keys)| {
+            if keys.iter().any(|k| key.pressed(*k)) {
+                actions.pressed.insert(*action);
+            }
+            if keys.iter().any(|k| key.just_pressed(*k)) {
+                actions.just_pressed.insert(*action);
+            }
+        });
+    }
+
+    // 1 人のときはどのゲームパッドでも 1P を動かす
+    let players = mode.players().max(1);
+    let mut pads: Vec<Gamepad> = gamepads.iter().copied().collect();
+    pads.sort_by_key(|gamepad| gamepad.0);
+    pads.iter().enumerate().for_each(|(i, gamepad)| {
+        let player = i % players;
+        bindings.buttons.iter().for_each(|(action, buttons)| {
+            let action = action.for_player(player);
+            buttons.iter().for_each(|b| {
+                let b = GamepadButton(*gamepad, *b);
+                if button.pressed(b) {
+                    actions.pressed.insert(action);
+                }
+                if button.just_pressed(b) {
+                    actions.just_pressed.insert(action);
+                }
+            });
+        });
+        let stick = |x, y| {
+            Vec2::new(
+                axis.get(GamepadAxis(*gamepad, x)).unwrap_or(0.0),
+                axis.get(GamepadAxis(*gamepad, y)).unwrap_or(0.0),
+            )
+        };
+        actions.move_axis[player] +=
+            stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+        actions.pan_axis += stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+    });
+
+    // Tick の間に離してしまった移動も、次に動けるときに使う
+    if *screen != Screen::Playing {
+        actions.clear_buffer();
+        return;
+    }
+    for player in 0..MAX_PLAYERS {
+        let stick = actions.stick_direction(player);
+        let tilted = stick != (0, 0) && stick != actions.stick[player];
+        actions.stick[player] = stick;
+        let pressed = actions
+            .just_pressed
+            .iter()
+            .any(|action| action.player() == player && action.direction().is_some());
+        if !(pressed || tilted) {
+            continue;
+        }
+        let direction = actions.move_direction(player);
+        actions.push_buffer(player, direction, bindings.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 次のフレームになって、押していたキーを離した
+    fn release(actions: &mut Actions) {
+        actions.pressed.clear();
+        actions.just_pressed.clear();
+    }
+
+    #[test]
+    fn zero_length_buffer_is_off() {
+        let mut actions = Actions::default();
+        actions.tap(Action::MoveUp, 0);
+        release(&mut actions);
+        assert_eq!(actions.take_move(0), (0, 0));
+    }
+
+    #[test]
+    fn full_buffer_drops_the_oldest() {
+        let mut actions = Actions::default();
+        actions.tap(Action::MoveUp, 2);
+        actions.tap(Action::MoveRight, 2);
+        actions.tap(Action::MoveDown, 2);
+        actions.tap(Action::P2MoveLeft, 2);
+        release(&mut actions);
+        assert_eq!(actions.take_move(0), (1, 0));
+        assert_eq!(actions.take_move(0), (0, -1));
+        assert_eq!(actions.take_move(0), (0, 0));
+        assert_eq!(actions.take_move(1), (-1, 0));
+    }
+
+    #[test]
+    fn empty_buffer_falls_back_to_move_direction() {
+        let mut actions = Actions::default();
+        actions.press(Action::MoveLeft);
+        actions.move_axis[0] = Vec2::new(0.0, 1.0);
+        assert_eq!(actions.take_move(0), (-1, 1));
+        assert_eq!(actions.take_move(0), actions.move_direction(0));
+
+        actions.tap(Action::MoveRight, 4);
+        actions.clear_buffer();
+        assert_eq!(actions.take_move(0), actions.move_direction(0));
+    }
+}
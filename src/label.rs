@@ -0,0 +1,109 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::input::*;
+use crate::title::Screen;
+use crate::Stamina;
+
+use bevy::prelude::*;
+
+pub struct LabelPlugin;
+
+impl Plugin for LabelPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LabelView::Hp)
+            .add_system(attach_label)
+            .add_system(toggle_label_view)
+            .add_system(label_text);
+    }
+}
+
+/// 生き物の上に何を表示するか
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LabelView {
+    Hp,
+    Satiety,
+    Age,
+    Stamina,
+    Hidden,
+}
+
+impl LabelView {
+    fn next(self) -> LabelView {
+        match self {
+            LabelView::Hp => LabelView::Satiety,
+            LabelView::Satiety => LabelView::Age,
+            LabelView::Age => LabelView::Stamina,
+            LabelView::Stamina => LabelView::Hidden,
+            LabelView::Hidden => LabelView::Hp,
+        }
+    }
+}
+
+/// 生き物の子 entity として付くラベル
+/// 親と一緒に動き、despawn_recursive で一緒に消える
+#[derive(Component)]
+pub struct StatusLabel;
+
+// HP を持つ生き物が spawn されたらラベルを付ける
+fn attach_label(
+    mut commands: Commands,
+    creature_query: Query<Entity, Added<HP>>,
+    asset_server: Res<AssetServer>,
+) {
+    creature_query.iter().for_each(|creature| {
+        commands.entity(creature).with_children(|parent| {
+            parent
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font_size: LABEL_FONT_SIZE,
+                            color: Color::BLACK,
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, 0.0, LABEL_LAYER_OFFSET),
+                    ..Default::default()
+                })
+                .insert(StatusLabel);
+        });
+    });
+}
+
+// 2P の移動と重ならないように Action で切り替える
+fn toggle_label_view(screen: Res<Screen>, actions: Res<Actions>, mut view: ResMut<LabelView>) {
+    if *screen == Screen::Playing && actions.just_pressed(Action::LabelView) {
+        *view = view.next();
+    }
+}
+
+fn label_text(
+    view: Res<LabelView>,
+    creature_query: Query<(&HP, &Satiety, Option<&Age>, Option<&Stamina>)>,
+    mut label_query: Query<(&Parent, &mut Text), With<StatusLabel>>,
+) {
+    label_query.iter_mut().for_each(|(parent, mut text)| {
+        let (hp, satiety, age, stamina) = match creature_query.get(parent.0) {
+            Ok(creature) => creature,
+            Err(_) => return,
+        };
+        let value = match *view {
+            LabelView::Hp => format!("{}", hp.val as i32),
+            LabelView::Satiety => format!("{}", satiety.val as i32),
+            LabelView::Age => age
+                .map(|age| format!("{}", age.val / GAME_DEFAULT_FPS))
+                .unwrap_or_default(),
+            LabelView::Stamina => stamina
+                .map(|stamina| format!("{}", stamina.val))
+                .unwrap_or_default(),
+            LabelView::Hidden => String::new(),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    });
+}
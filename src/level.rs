@@ -0,0 +1,447 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::mission::Objective;
+use crate::ship::*;
+use std::collections::HashSet;
+
+/*
+ * Level: 島の形と最初の生き物、船の航路
+ *
+ * ファイルは 1 行 1 項目のテキスト
+ *   name island
+ *   field <x0> <x1> <y>        x0..=x1 のマスが島
+ *   player <x> <y>
+ *   creature <species> <x> <y>
+ *   random <species> <count>   seed で決まる島のどこかに置く
+ *   source <x> <y>             Walnut がこの付近に生える
+ *   walnut_rate <probability>  tick ごとに Walnut が生える確率 (5% = 500)
+ *   ship <x> <y> <first_arrival> <interval> <docking> <route>..
+ *                              (x, y) の Terminal に寄港する船。route は R12 D48 のように書く
+ *                              route を省くと島の外周を一周する
+ *   objective <kind> ..        クリア条件。書き方は mission.rs を見る
+ */
+#[derive(Clone)]
+pub struct Level {
+    pub name: String,
+    pub fields: Vec<Position>,
+    pub player: Position,
+    pub creatures: Vec<(Species, Position)>,
+    pub random: Vec<(Species, u32)>,
+    pub walnut_sources: Vec<Position>,
+    pub walnut_rate: u32,
+    pub lines: Vec<ShipLine>,
+    pub objectives: Vec<Objective>,
+}
+
+pub fn grid(x: i32, y: i32) -> Position {
+    Position { x, y, z: 0 }
+}
+
+impl Level {
+    /// いつもの長方形の島
+    pub fn island() -> Level {
+        let mut fields = vec![];
+        for x in FIELD_LEFTBTM_X..FIELD_LEFTBTM_X + FIELD_WIDTH as i32 {
+            for y in FIELD_LEFTBTM_Y..FIELD_LEFTBTM_Y + FIELD_HEIGHT as i32 {
+                fields.push(grid(x, y));
+            }
+        }
+        let terminal = grid(
+            FIELD_WIDTH as i32 + FIELD_LEFTBTM_X - 1,
+            FIELD_HEIGHT as i32 + FIELD_LEFTBTM_Y - 1,
+        );
+        let route = coastline_route(&fields, terminal);
+        Level {
+            name: DEFAULT_LEVEL.to_string(),
+            fields,
+            player: grid(4, 6),
+            creatures: vec![],
+            random: vec![
+                (Species::StrongBear, INITIAL_BEAR_NUM),
+                (Species::Fox, INITIAL_FOX_NUM),
+                (Species::Walnut, INITIAL_WALNUT_NUM),
+            ],
+            walnut_sources: vec![],
+            walnut_rate: PROBABILITY_INCREASE_WALNUT,
+            lines: vec![ShipLine {
+                terminal,
+                route,
+                first_arrival: SHIP_FIRST_ARRIVAL,
+                interval: SHIP_INTERVAL,
+                docking: SHIP_DOCKING,
+            }],
+            objectives: vec![],
+        }
+    }
+
+    /// 何も無いところから作り始めるとき用
+    pub fn empty(name: &str) -> Level {
+        Level {
+            name: name.to_string(),
+            fields: vec![],
+            player: grid(FIELD_LEFTBTM_X, FIELD_LEFTBTM_Y),
+            creatures: vec![],
+            random: vec![],
+            walnut_sources: vec![],
+            walnut_rate: PROBABILITY_INCREASE_WALNUT,
+            lines: vec![],
+            objectives: vec![],
+        }
+    }
+
+    pub fn is_field(&self, pos: &Position) -> bool {
+        self.fields.contains(pos)
+    }
+
+    pub fn parse(text: &str) -> Result<Level, String> {
+        let mut level = Level::empty("");
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            level
+                .parse_line(line)
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+        }
+        // 島の形がわかってから外周を回る航路を作る
+        let fields = level.fields.clone();
+        level
+            .lines
+            .iter_mut()
+            .filter(|line| line.route.is_empty())
+            .for_each(|line| line.route = coastline_route(&fields, line.terminal));
+        Ok(level)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| -> Result<i32, String> {
+            cols.get(i)
+                .ok_or(format!("missing column {}", i))?
+                .parse::<i32>()
+                .map_err(|e| e.to_string())
+        };
+        // 数や確率。負の数はループが終わらなくなるので受け付けない
+        let count = |i: usize| -> Result<u32, String> {
+            let n = num(i)?;
+            u32::try_from(n).map_err(|_| format!("negative number {}", n))
+        };
+        let species = |i: usize| -> Result<Species, String> {
+            let name = cols.get(i).ok_or(format!("missing column {}", i))?;
+            Species::from_name(name).ok_or(format!("unknown species {}", name))
+        };
+
+        match cols[0] {
+            "name" => self.name = cols[1..].join(" "),
+            "field" => {
+                let y = num(3)?;
+                for x in num(1)?..=num(2)? {
+                    self.fields.push(grid(x, y));
+                }
+            }
+            "player" => self.player = grid(num(1)?, num(2)?),
+            "creature" => self.creatures.push((species(1)?, grid(num(2)?, num(3)?))),
+            "random" => self.random.push((species(1)?, count(2)?)),
+            "source" => self.walnut_sources.push(grid(num(1)?, num(2)?)),
+            "walnut_rate" => self.walnut_rate = count(1)?,
+            "ship" => {
+                let mut route = vec![];
+                for token in cols.get(6..).unwrap_or(&[]) {
+                    let mut chars = token.chars();
+                    let offset = match chars.next() {
+                        Some('U') => Position::up(),
+                        Some('D') => Position::down(),
+                        Some('L') => Position::left(),
+                        Some('R') => Position::right(),
+                        Some('S') => Position::stay(),
+                        _ => return Err(format!("unknown direction {}", token)),
+                    };
+                    let count: usize = chars
+                        .as_str()
+                        .parse()
+                        .map_err(|_| format!("bad route {}", token))?;
+                    route.extend(std::iter::repeat(offset).take(count));
+                }
+                self.lines.push(ShipLine {
+                    terminal: grid(num(1)?, num(2)?),
+                    route,
+                    first_arrival: num(3)?,
+                    interval: num(4)?,
+                    docking: num(5)?,
+                });
+            }
+            "objective" => self.objectives.push(Objective::parse(&cols[1..])?),
+            other => return Err(format!("unknown item {}", other)),
+        }
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![format!("name {}", self.name)];
+
+        // 行ごとに、続いているマスをまとめて書く
+        let mut fields = self.fields.clone();
+        fields.sort_by_key(|pos| (pos.y, pos.x));
+        fields.dedup();
+        let mut i = 0;
+        while i < fields.len() {
+            let start = fields[i];
+            let mut end = start;
+            while i + 1 < fields.len() && fields[i + 1].y == end.y && fields[i + 1].x == end.x + 1 {
+                i += 1;
+                end = fields[i];
+            }
+            lines.push(format!("field {} {} {}", start.x, end.x, start.y));
+            i += 1;
+        }
+
+        lines.push(format!("player {} {}", self.player.x, self.player.y));
+        self.creatures.iter().for_each(|(species, pos)| {
+            lines.push(format!("creature {} {} {}", species.name(), pos.x, pos.y));
+        });
+        self.random.iter().for_each(|(species, count)| {
+            lines.push(format!("random {} {}", species.name(), count));
+        });
+        self.walnut_sources.iter().for_each(|pos| {
+            lines.push(format!("source {} {}", pos.x, pos.y));
+        });
+        if self.walnut_rate != PROBABILITY_INCREASE_WALNUT {
+            lines.push(format!("walnut_rate {}", self.walnut_rate));
+        }
+        self.lines.iter().for_each(|line| {
+            lines.push(format!(
+                "ship {} {} {} {} {} {}",
+                line.terminal.x,
+                line.terminal.y,
+                line.first_arrival,
+                line.interval,
+                line.docking,
+                route_text(&line.route)
+            ));
+        });
+        self.objectives.iter().for_each(|objective| {
+            lines.push(format!("objective {}", objective.to_text()));
+        });
+        lines.join("\n") + "\n"
+    }
+
+    /// 遊べる Level か調べる
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fields.is_empty() {
+            return Err("no field".to_string());
+        }
+        if !self.is_field(&self.player) {
+            return Err("player is not on the field".to_string());
+        }
+        if let Some((species, _)) = self.creatures.iter().find(|(_, pos)| !self.is_field(pos)) {
+            return Err(format!("{} is not on the field", species.name()));
+        }
+        if self.lines.is_empty() {
+            return Err("no ship".to_string());
+        }
+        for (i, line) in self.lines.iter().enumerate() {
+            if !self.is_field(&line.terminal) {
+                return Err(format!("terminal of ship{} is not on the field", i + 1));
+            }
+            if line.route.is_empty() {
+                return Err(format!("ship{} has no route", i + 1));
+            }
+            if line
+                .route
+                .iter()
+                .any(|step| step.x.abs() > 1 || step.y.abs() > 1)
+            {
+                return Err(format!("ship{} jumps on its route", i + 1));
+            }
+            // 航路を一周したら Terminal に戻ってこないと乗れない
+            let end = line
+                .route
+                .iter()
+                .fold(line.terminal, |pos, step| &pos + step);
+            if end != line.terminal {
+                return Err(format!("ship{} does not come back to its terminal", i + 1));
+            }
+            if line.docking <= 0 || line.interval <= line.docking {
+                return Err(format!("ship{} has a broken timetable", i + 1));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn route_text(route: &[Position]) -> String {
+    let mut tokens: Vec<(char, usize)> = vec![];
+    route.iter().for_each(|step| {
+        let dir = match (step.x, step.y) {
+            (0, 1) => 'U',
+            (0, -1) => 'D',
+            (-1, 0) => 'L',
+            (1, 0) => 'R',
+            _ => 'S',
+        };
+        match tokens.last_mut() {
+            Some((last, count)) if *last == dir => *count += 1,
+            _ => tokens.push((dir, 1)),
+        }
+    });
+    tokens
+        .iter()
+        .map(|(dir, count)| format!("{}{}", dir, count))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// 時計回り (y は上向き) に並べた周囲 8 マス
+const AROUND: [(i32, i32); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
+/// 島を 1 マス広げた外周。船はここを一周する
+pub fn coastline(fields: &[Position]) -> Vec<Position> {
+    let land: HashSet<(i32, i32)> = fields.iter().map(|pos| (pos.x, pos.y)).collect();
+    let outline: HashSet<(i32, i32)> = land
+        .iter()
+        .flat_map(|&(x, y)| AROUND.iter().map(move |(dx, dy)| (x + dx, y + dy)))
+        .chain(land.iter().copied())
+        .collect();
+
+    // Moore-Neighbor tracing。一番下の行の左端から外側をなぞる
+    let start = match outline.iter().min_by_key(|&&(x, y)| (y, x)) {
+        Some(start) => *start,
+        None => return vec![],
+    };
+    let mut contour = vec![start];
+    let mut current = start;
+    // 直前に調べた外側のマスの向き。start の左は必ず外側
+    let mut back = 0;
+    for _ in 0..outline.len() * AROUND.len() {
+        let next = (1..=AROUND.len()).find_map(|k| {
+            let (dx, dy) = AROUND[(back + k) % AROUND.len()];
+            let next = (current.0 + dx, current.1 + dy);
+            if !outline.contains(&next) {
+                return None;
+            }
+            let (px, py) = AROUND[(back + k - 1) % AROUND.len()];
+            let outside = (current.0 + px, current.1 + py);
+            let next_back = AROUND
+                .iter()
+                .position(|(dx, dy)| (next.0 + dx, next.1 + dy) == outside)?;
+            Some((next, next_back))
+        });
+        let (next, next_back) = match next {
+            Some(next) => next,
+            None => break,
+        };
+        // 一周して同じ向きに出ようとしたら終わり
+        if current == start && contour.len() > 1 && next == contour[1] {
+            contour.pop();
+            break;
+        }
+        contour.push(next);
+        current = next;
+        back = next_back;
+    }
+    contour.into_iter().map(|(x, y)| grid(x, y)).collect()
+}
+
+// 1 マスずつ近づく
+fn toward(from: Position, to: Position) -> Position {
+    grid((to.x - from.x).signum(), (to.y - from.y).signum())
+}
+
+/// terminal から一番近い外周に出て、島を一周して terminal に戻る航路
+pub fn coastline_route(fields: &[Position], terminal: Position) -> Vec<Position> {
+    let contour = coastline(fields);
+    let start = match (0..contour.len()).min_by_key(|&i| {
+        let (dx, dy) = (contour[i].x - terminal.x, contour[i].y - terminal.y);
+        dx * dx + dy * dy
+    }) {
+        Some(start) => start,
+        None => return vec![],
+    };
+
+    let mut route = vec![];
+    let mut pos = terminal;
+    let mut sail_to = |to: Position, route: &mut Vec<Position>| {
+        while pos != to {
+            let step = toward(pos, to);
+            route.push(step);
+            pos = &pos + &step;
+        }
+    };
+    for i in 0..=contour.len() {
+        sail_to(contour[(start + i) % contour.len()], &mut route);
+    }
+    sail_to(terminal, &mut route);
+    route
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn island_round_trips_through_text() {
+        let island = Level::island();
+        let text = island.to_text();
+        let parsed = Level::parse(&text).unwrap();
+        assert_eq!(parsed.to_text(), text);
+        assert_eq!(parsed.fields.len(), island.fields.len());
+        assert_eq!(parsed.lines[0].route, island.lines[0].route);
+    }
+
+    #[test]
+    fn ship_without_route_sails_around_the_island() {
+        let text = "field 0 3 0\nfield 0 3 1\nplayer 0 0\nship 3 1 10 100 20\n";
+        let level = Level::parse(text).unwrap();
+        assert!(!level.lines[0].route.is_empty());
+        assert_eq!(level.validate(), Ok(()));
+    }
+
+    #[test]
+    fn broken_lines_are_errors() {
+        [
+            "ship 1 2",
+            "ship 1 2 3 4 5 \u{e9}3",
+            "ship 1 2 3 4 5 R",
+            "random fox -1",
+            "walnut_rate -5",
+            "creature dragon 1 1",
+            "player 1",
+            "volcano 1 2",
+            "objective eat strong_bear 1",
+        ]
+        .iter()
+        .for_each(|line| assert!(Level::parse(line).is_err(), "{}", line));
+    }
+
+    #[test]
+    fn validate_finds_unplayable_levels() {
+        assert_eq!(Level::island().validate(), Ok(()));
+        assert!(Level::empty("empty").validate().is_err());
+
+        let mut level = Level::island();
+        level.player = grid(-10, -10);
+        assert!(level.validate().is_err());
+
+        let mut level = Level::island();
+        level.lines.clear();
+        assert!(level.validate().is_err());
+
+        let mut level = Level::island();
+        level.lines[0].route.pop();
+        assert!(level.validate().is_err());
+
+        let mut level = Level::island();
+        level.lines[0].interval = level.lines[0].docking;
+        assert!(level.validate().is_err());
+    }
+}
@@ -1,11 +1,58 @@
+pub mod artwork;
+pub mod bag;
+pub mod camera;
+pub mod campaign;
+pub mod character;
 pub mod components;
 pub mod consts;
+pub mod craft;
+pub mod difficulty;
 pub mod eat;
-
+pub mod editor;
+pub mod generator;
+pub mod highscore;
+pub mod hud;
+pub mod input;
+pub mod label;
+pub mod level;
+pub mod mission;
+pub mod net;
+pub mod observer;
+pub mod score;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+pub mod ship;
+pub mod simulation;
+pub mod storage;
+pub mod title;
+pub mod touch;
+pub mod tween;
+
+use crate::artwork::*;
+use crate::bag::*;
+use crate::camera::*;
+use crate::campaign::*;
+use crate::character::*;
 use crate::components::*;
 use crate::consts::*;
+use crate::craft::*;
+use crate::difficulty::*;
 use crate::eat::*;
-use array_macro::*;
+use crate::editor::*;
+use crate::highscore::*;
+use crate::hud::*;
+use crate::input::*;
+use crate::label::*;
+use crate::level::*;
+use crate::mission::*;
+use crate::net::*;
+use crate::observer::*;
+use crate::score::*;
+use crate::ship::*;
+use crate::simulation::*;
+use crate::title::*;
+use crate::touch::*;
+use crate::tween::*;
 use std::collections::HashSet;
 
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
@@ -14,26 +61,64 @@ use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use rand::prelude::*;
 
-pub const SHIP_MOVING: [Position; 120] = array![x => match x {
-    0..=11 => Position::right(),
-    12..=59 => Position::down(),
-    60..=107 => Position::up(),
-    108..=119 => Position::left(),
-    _ => Position::stay(),
-}; 120];
-
 #[derive(Component)]
 struct State {
     kind: StateKind,
 }
 
+#[derive(Clone, Copy, PartialEq)]
 enum StateKind {
     GameOver,
     GameClear,
     Playing,
+    Observing,
+    // Versus で先に船に乗った Player の id
+    Won(usize),
+}
+
+/// ゲーム開始からの tick 数
+pub struct GameClock {
+    pub tick: i32,
+}
+
+/// 遊ぶ島。seed が同じなら最初の配置も同じになる
+pub struct Stage {
+    pub level: Level,
+    pub seed: u64,
 }
 
-struct GameTimer(Timer);
+/// タイトル画面などからゲームを始める
+pub struct StartGame;
+
+/// Observer では Player を置かずに生態系だけを眺める
+/// Coop は生き残った全員が島を出ればクリア、Versus は先に船に乗った方の勝ち
+/// Online はサーバーの島を映すだけ、Server は画面を出さずに島を動かす
+/// Campaign は Normal と同じだが、クリアすると次の島へ進む
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    Normal,
+    Campaign,
+    Observer,
+    Coop,
+    Versus,
+    Online,
+    Server,
+}
+
+impl GameMode {
+    pub fn players(&self) -> usize {
+        match self {
+            GameMode::Normal | GameMode::Campaign | GameMode::Online => 1,
+            // サーバーの Player は参加したときに置く
+            GameMode::Observer | GameMode::Server => 0,
+            GameMode::Coop | GameMode::Versus => 2,
+        }
+    }
+}
+
+/// 船に乗って島を出た Player の id と HP
+#[derive(Default)]
+pub struct Escaped(Vec<(usize, f32)>);
 
 #[derive(Component)]
 pub struct Stamina {
@@ -74,15 +159,15 @@ impl Stamina {
             val: 0,
         }
     }
-    fn walnut() -> Stamina {
+    fn squirrel() -> Stamina {
         Stamina {
-            healing_val: HEALING_STAMINA_WALNUT,
+            healing_val: HEALING_STAMINA_SQUIRREL,
             val: 0,
         }
     }
-    fn ship() -> Stamina {
+    fn walnut() -> Stamina {
         Stamina {
-            healing_val: HEALING_STAMINA_SHIP,
+            healing_val: HEALING_STAMINA_WALNUT,
             val: 0,
         }
     }
@@ -116,155 +201,253 @@ fn get_increase_pos(pos: &Position, range: u32) -> Position {
     }
 }
 
-fn get_random_grid() -> IVec2 {
-    let mut rng = rand::thread_rng();
-    let x = rng.gen_range(FIELD_LEFTBTM_X..FIELD_LEFTBTM_X as i32 + FIELD_WIDTH as i32);
-    let y = rng.gen_range(FIELD_LEFTBTM_Y..FIELD_LEFTBTM_Y as i32 + FIELD_HEIGHT as i32);
-    IVec2::new(x, y)
+fn get_random_grid<R: Rng>(level: &Level, rng: &mut R) -> Option<Position> {
+    level.fields.choose(rng).copied()
 }
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 1.0)))
+    // cargo run -- --server [port] で画面を出さないサーバーになる
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(port) = server::port_from_args() {
+        return server::run(port);
+    }
+
+    let mut app = App::new();
+    app.insert_resource(ClearColor(Color::rgb(0.4, 0.4, 1.0)))
         .insert_resource(WindowDescriptor {
             title: "FoodChainGame".to_string(),
             width: (SCREEN_WIDTH * UNIT_WIDTH) as f32,
             height: (SCREEN_HEIGHT * UNIT_HEIGHT) as f32,
             ..Default::default()
         })
-        .insert_resource(GameTimer(Timer::new(
-            std::time::Duration::from_millis(GAME_TICK),
-            true,
-        )))
         .add_plugins(DefaultPlugins)
-        .add_plugin(ShapePlugin)
-        .add_plugin(HpPlugin)
+        .add_plugin(ShapePlugin);
+    add_game(&mut app)
+        .add_plugin(TitlePlugin)
+        .add_plugin(HighScorePlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(LabelPlugin)
+        .add_plugin(ArtworkPlugin)
+        .add_plugin(TweenPlugin)
+        .add_plugin(ObserverPlugin)
+        .add_plugin(EditorPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(TouchPlugin)
+        .add_plugin(NetPlugin)
+        .add_plugin(CampaignPlugin)
         .add_startup_system(setup_system)
-        .add_system(heal)
-        .add_system(get_old)
-        .add_system_set(
-            SystemSet::new()
-                .after("eaten")
-                .with_system(move_player)
-                .with_system(move_fox)
-                .with_system(move_strong_bear)
-                .with_system(move_weak_bear)
-                .with_system(move_ship),
-        )
-        .add_system(increase_strong_bear)
-        .add_system(increase_fox)
-        .add_system(increase_walnut)
         .add_system(text_value)
-        .add_system(game_timer)
-        .add_system(goal)
-        .add_system(despawn_hp_text)
-        .add_system(spawn_all_hp_text)
-        //.add_system(spawn_all_satiety_text)
         .add_system(position_transform)
-        .add_system(weaken_bear)
-        .add_system(die_of_old_age)
-        .add_system(restart)
-        .add_system(despawn.after("eaten"))
         //.add_plugin(LogDiagnosticsPlugin::default())
         //.add_plugin(FrameTimeDiagnosticsPlugin::default())
         .run();
 }
 
+/// 島を動かすのに要るもの。画面の無いサーバーでも使う
+pub fn add_game(app: &mut App) -> &mut App {
+    app.insert_resource(GameClock { tick: 0 })
+        .insert_resource(Record::default())
+        .insert_resource(Escaped::default())
+        .insert_resource(Stage {
+            level: Level::island(),
+            seed: DEFAULT_SEED,
+        })
+        .insert_resource(GameMode::Normal)
+        .insert_resource(Character::default())
+        .add_event::<StartGame>()
+        .add_plugin(ActionPlugin)
+        .add_plugin(SimulationPlugin)
+        .add_plugin(HpPlugin)
+        .add_plugin(BagPlugin)
+        .add_plugin(CraftPlugin)
+        .add_plugin(ShipPlugin)
+        .add_plugin(MissionPlugin)
+        .add_plugin(DifficultyPlugin)
+        .add_system_set_to_stage(
+            SimulationStage,
+            tick_set(Tick::Age)
+                .with_system(heal)
+                .with_system(get_old)
+                .with_system(die_of_old_age),
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            tick_set(Tick::Death)
+                .with_system(despawn)
+                .with_system(weaken_bear),
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            tick_set(Tick::Reproduce)
+                .with_system(increase_strong_bear)
+                .with_system(increase_fox)
+                .with_system(increase_walnut),
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            tick_set(Tick::Move)
+                .with_system(move_player)
+                .with_system(move_fox)
+                .with_system(move_strong_bear)
+                .with_system(move_weak_bear),
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            tick_set(Tick::Goal).with_system(goal.after("objectives")),
+        )
+        .add_system(restart)
+}
+
 fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-    commands.spawn_bundle(UiCameraBundle::default());
-    initial_spawn(&mut commands, &asset_server)
+    spawn_cameras(&mut commands);
+    spawn_title(&mut commands, &asset_server)
 }
 
-fn initial_spawn(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    for i in 0..FIELD_WIDTH as i32 {
-        for j in 0..FIELD_HEIGHT as i32 {
-            spawn_field(
-                commands,
-                Position {
-                    x: i + FIELD_LEFTBTM_X,
-                    y: j + FIELD_LEFTBTM_Y,
-                    z: FIELD_LAYER,
-                },
-            );
+fn initial_spawn(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    timetable: &Timetable,
+    stage: &Stage,
+    mode: GameMode,
+    character: Character,
+    difficulty: Difficulty,
+) {
+    let level = &stage.level;
+    let mut rng = StdRng::seed_from_u64(stage.seed);
+    level.fields.iter().for_each(|pos| {
+        spawn_field(
+            commands,
+            Position {
+                z: FIELD_LAYER,
+                ..*pos
+            },
+        );
+    });
+    spawn_harbor(commands, timetable, mode.players(), asset_server);
+    spawn_hud(commands, asset_server, mode.players());
+    spawn_simulation_indicator(commands, asset_server);
+    spawn_minimap(commands);
+    spawn_mission_text(commands, asset_server);
+    match mode {
+        GameMode::Observer => spawn_brush_text(commands, asset_server),
+        // Player も生き物もサーバーから届いたときに置く
+        GameMode::Online => spawn_dpad(commands, asset_server),
+        GameMode::Server => {}
+        _ => {
+            player_starts(level, mode.players())
+                .into_iter()
+                .enumerate()
+                .for_each(|(id, pos)| {
+                    spawn_player(
+                        commands,
+                        Position {
+                            z: PLAYER_LAYER,
+                            ..pos
+                        },
+                        id,
+                        character.species(mode),
+                        &asset_server,
+                    );
+                });
+            spawn_dpad(commands, asset_server);
         }
     }
-    spawn_terminal(
-        commands,
-        Position {
-            x: FIELD_WIDTH as i32 + FIELD_LEFTBTM_X as i32 - 1,
-            y: FIELD_HEIGHT as i32 + FIELD_LEFTBTM_Y as i32 - 1,
-            z: TERMINAL_LAYER,
-        },
-    );
-    spawn_ship(
+    if mode != GameMode::Online {
+        level.creatures.iter().for_each(|(species, pos)| {
+            spawn_species(commands, *species, *pos, asset_server);
+        });
+        level.random.iter().for_each(|(species, count)| {
+            let count = match species {
+                Species::StrongBear => difficulty.bears(*count),
+                _ => *count,
+            };
+            for _ in 0..count {
+                if let Some(grid) = get_random_grid(level, &mut rng) {
+                    spawn_species(commands, *species, grid, asset_server);
+                }
+            }
+        });
+        // 材料は道具を作れる人間がいるときだけ置く
+        if crafting(mode) && character.species(mode) == Species::Human {
+            spawn_materials(commands, level, &mut rng);
+        }
+    }
+    spawn_text(
         commands,
         Position {
-            x: FIELD_WIDTH as i32 + FIELD_LEFTBTM_X as i32 - 1,
-            y: FIELD_HEIGHT as i32 + FIELD_LEFTBTM_Y as i32 - 1,
-            z: PLAYER_LAYER,
+            x: 10,
+            y: 10,
+            z: TEXT_LAYER,
         },
-    );
-    spawn_player(
-        commands,
-        Position {
-            x: 4,
-            y: 6,
-            z: PLAYER_LAYER,
+        match mode {
+            GameMode::Observer => StateKind::Observing,
+            _ => StateKind::Playing,
         },
         &asset_server,
     );
-    for _ in 0..INITIAL_BEAR_NUM {
-        let grid = get_random_grid();
-        spawn_strong_bear(
+}
+
+// 2P 以降は 1P の近くの島から始める
+fn player_starts(level: &Level, players: usize) -> Vec<Position> {
+    let mut others: Vec<Position> = level
+        .fields
+        .iter()
+        .copied()
+        .filter(|pos| *pos != level.player)
+        .collect();
+    others.sort_by_key(|pos| (distance(pos, &level.player), pos.y, pos.x));
+    std::iter::once(level.player)
+        .chain(others)
+        .take(players)
+        .collect()
+}
+
+/// 生き物を種類ごとのレイヤーに置く。Player は Level.player で置くのでここでは扱わない
+/// Squirrel は Player が選んだときだけの生き物なので置かない
+pub fn spawn_species(
+    commands: &mut Commands,
+    species: Species,
+    grid: Position,
+    asset_server: &Res<AssetServer>,
+) -> Option<Entity> {
+    let entity = match species {
+        Species::Walnut => spawn_walnut(
             commands,
             Position {
-                x: grid[0],
-                y: grid[1],
-                z: BEAR_LAYER,
+                z: PLAYER_LAYER,
+                ..grid
             },
-            &asset_server,
-            MAX_HP_BEAR,
-        );
-    }
-    for _ in 0..INITIAL_FOX_NUM {
-        let grid = get_random_grid();
-        spawn_fox(
+            asset_server,
+        ),
+        Species::Fox => spawn_fox(
             commands,
             Position {
-                x: grid[0],
-                y: grid[1],
                 z: PLAYER_LAYER,
+                ..grid
             },
-            &asset_server,
-        );
-    }
-    for _ in 0..INITIAL_WALNUT_NUM {
-        let grid = get_random_grid();
-        spawn_walnut(
+            asset_server,
+        ),
+        Species::StrongBear => spawn_strong_bear(
             commands,
             Position {
-                x: grid[0],
-                y: grid[1],
-                z: PLAYER_LAYER,
+                z: BEAR_LAYER,
+                ..grid
             },
-            &asset_server,
-        );
-    }
-    spawn_text(
-        commands,
-        Position {
-            x: 10,
-            y: 10,
-            z: TEXT_LAYER,
-        },
-        StateKind::Playing,
-        &asset_server,
-    );
-}
-
-fn game_timer(time: Res<Time>, mut timer: ResMut<GameTimer>) {
-    timer.0.tick(time.delta());
+            asset_server,
+            MAX_HP_BEAR,
+        ),
+        Species::WeakBear => spawn_weak_bear(
+            commands,
+            Position {
+                z: BEAR_LAYER,
+                ..grid
+            },
+            asset_server,
+            MAX_HP_BEAR,
+        ),
+        Species::Human | Species::Squirrel => return None,
+    };
+    Some(entity)
 }
 
 fn get_render_position(pos: &Position) -> Position {
@@ -277,7 +460,8 @@ fn get_render_position(pos: &Position) -> Position {
     };
 }
 
-fn position_transform(mut position_query: Query<(&Position, &mut Transform)>) {
+// 動くものは Tween で補間して描く
+fn position_transform(mut position_query: Query<(&Position, &mut Transform), Without<Tween>>) {
     position_query.iter_mut().for_each(|(pos, mut transform)| {
         let render_pos = get_render_position(pos);
         transform.translation = Vec3::new(
@@ -294,6 +478,8 @@ fn text_value(mut state_query: Query<(&State, &mut Text)>) {
             StateKind::GameOver => "GameOver!!!!".to_string(),
             StateKind::GameClear => "GameClear!".to_string(),
             StateKind::Playing => "Playing!".to_string(),
+            StateKind::Observing => "Observing".to_string(),
+            StateKind::Won(id) => format!("Player {} wins!", id + 1),
         }
     });
 }
@@ -339,46 +525,52 @@ fn spawn_field(commands: &mut Commands, position: Position) {
         .insert(Field);
 }
 
-fn spawn_terminal(commands: &mut Commands, position: Position) {
-    let shape = shapes::Rectangle {
-        extents: Vec2::new(UNIT_WIDTH as f32, UNIT_HEIGHT as f32),
-        ..shapes::Rectangle::default()
-    };
-
-    commands
-        .spawn_bundle(GeometryBuilder::build_as(
-            &shape,
-            DrawMode::Outlined {
-                fill_mode: FillMode::color(Color::rgb(0.7, 0.7, 1.0)),
-                outline_mode: StrokeMode::new(Color::BLACK, 0.0),
-            },
-            Transform::default(),
-        ))
-        .insert(position)
-        .insert(Terminal);
-}
-
-fn spawn_ship(commands: &mut Commands, position: Position) {
-    let shape = shapes::Rectangle {
-        extents: Vec2::new(UNIT_WIDTH as f32, UNIT_HEIGHT as f32),
-        ..shapes::Rectangle::default()
+/// 人間以外を選んだら、島の生き物と同じ Eater / Prey と Stamina を持った Player にする
+fn spawn_player(
+    commands: &mut Commands,
+    position: Position,
+    id: usize,
+    species: Species,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
+    let color = PLAYER_COLORS[id % PLAYER_COLORS.len()];
+    let player = match species {
+        Species::Fox => spawn_fox(commands, position, asset_server),
+        Species::StrongBear => {
+            let bear = spawn_strong_bear(
+                commands,
+                Position {
+                    z: BEAR_LAYER,
+                    ..position
+                },
+                asset_server,
+                MAX_HP_BEAR,
+            );
+            // Player の熊は年をとって WeakBear にならない
+            commands.entity(bear).remove::<Age>();
+            bear
+        }
+        Species::Squirrel => spawn_squirrel(commands, position, asset_server),
+        _ => return spawn_human(commands, position, id, asset_server),
     };
-
+    // 島の生き物と見分けられるように Player の色で縁どる
     commands
-        .spawn_bundle(GeometryBuilder::build_as(
-            &shape,
-            DrawMode::Outlined {
-                fill_mode: FillMode::color(Color::WHITE),
-                outline_mode: StrokeMode::new(Color::BLACK, 0.0),
-            },
-            Transform::default(),
-        ))
-        .insert(position)
-        .insert(Ship { index: 0 })
-        .insert(Stamina::ship());
+        .entity(player)
+        .insert(Player { id })
+        .insert(Bag::default())
+        .insert(DrawMode::Outlined {
+            fill_mode: FillMode::color(marker_color(species)),
+            outline_mode: StrokeMode::new(color, 3.0),
+        });
+    player
 }
 
-fn spawn_player(commands: &mut Commands, position: Position, asset_server: &Res<AssetServer>) {
+fn spawn_human(
+    commands: &mut Commands,
+    position: Position,
+    id: usize,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
     let shape = shapes::Circle {
         radius: (UNIT_WIDTH / 2) as f32,
         center: Vec2::new(0.0, 0.0),
@@ -389,7 +581,7 @@ fn spawn_player(commands: &mut Commands, position: Position, asset_server: &Res<
         .spawn_bundle(GeometryBuilder::build_as(
             &shape,
             DrawMode::Outlined {
-                fill_mode: FillMode::color(Color::PURPLE),
+                fill_mode: FillMode::color(PLAYER_COLORS[id % PLAYER_COLORS.len()]),
                 outline_mode: StrokeMode::new(Color::BLACK, 0.0),
             },
             Transform {
@@ -397,8 +589,11 @@ fn spawn_player(commands: &mut Commands, position: Position, asset_server: &Res<
                 ..Default::default()
             },
         ))
-        .insert(Player)
+        .insert(Player { id })
+        .insert(Bag::default())
+        .insert(Tools::default())
         .insert(Human)
+        .insert(Species::Human)
         .insert(WalnutEater)
         .insert(FoxEater)
         .insert(WeakBearEater)
@@ -406,7 +601,8 @@ fn spawn_player(commands: &mut Commands, position: Position, asset_server: &Res<
         .insert(position)
         .insert(Stamina::human())
         .insert(HP::human())
-        .insert(Satiety::human());
+        .insert(Satiety::human())
+        .id()
 }
 
 fn spawn_strong_bear(
@@ -414,7 +610,7 @@ fn spawn_strong_bear(
     position: Position,
     asset_server: &Res<AssetServer>,
     hp: f32,
-) {
+) -> Entity {
     let shape = shapes::Circle {
         radius: (UNIT_WIDTH / 2) as f32,
         center: Vec2::new(0.0, 0.0),
@@ -435,12 +631,15 @@ fn spawn_strong_bear(
         ))
         .insert(StrongBear)
         .insert(FoxEater)
+        .insert(SquirrelEater)
+        .insert(Species::StrongBear)
         .insert(HumanEater)
         .insert(position)
         .insert(Stamina::strong_bear())
         .insert(HP::bear(hp))
         .insert(Satiety::strong_bear())
-        .insert(Age { val: 0 });
+        .insert(Age { val: 0 })
+        .id()
 }
 
 fn spawn_weak_bear(
@@ -448,7 +647,7 @@ fn spawn_weak_bear(
     position: Position,
     asset_server: &Res<AssetServer>,
     hp: f32,
-) {
+) -> Entity {
     let shape = shapes::Circle {
         radius: (UNIT_WIDTH / 2) as f32,
         center: Vec2::new(0.0, 0.0),
@@ -469,6 +668,8 @@ fn spawn_weak_bear(
         ))
         .insert(WeakBear)
         .insert(FoxEater)
+        .insert(SquirrelEater)
+        .insert(Species::WeakBear)
         .insert(HumanPrey)
         .insert(position)
         .insert(Stamina::weak_bear())
@@ -476,10 +677,15 @@ fn spawn_weak_bear(
         .insert(Satiety::weak_bear())
         .insert(Age {
             val: HEALTHSPAN_STRONG_BEAR,
-        });
+        })
+        .id()
 }
 
-fn spawn_fox(commands: &mut Commands, position: Position, asset_server: &Res<AssetServer>) {
+fn spawn_fox(
+    commands: &mut Commands,
+    position: Position,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
     let shape = shapes::Circle {
         radius: (UNIT_WIDTH / 2) as f32,
         center: Vec2::new(0.0, 0.0),
@@ -500,16 +706,59 @@ fn spawn_fox(commands: &mut Commands, position: Position, asset_server: &Res<Ass
         ))
         .insert(Fox)
         .insert(WalnutEater)
+        .insert(SquirrelEater)
+        .insert(Species::Fox)
         .insert(StrongBearPrey)
         .insert(WeakBearPrey)
         .insert(HumanPrey)
         .insert(position)
         .insert(Stamina::fox())
         .insert(HP::fox())
-        .insert(Satiety::fox());
+        .insert(Satiety::fox())
+        .id()
 }
 
-fn spawn_walnut(commands: &mut Commands, position: Position, asset_server: &Res<AssetServer>) {
+fn spawn_squirrel(
+    commands: &mut Commands,
+    position: Position,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
+    let shape = shapes::Circle {
+        radius: (UNIT_WIDTH / 2) as f32,
+        center: Vec2::new(0.0, 0.0),
+    };
+    let render_pos = get_render_position(&position);
+
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(marker_color(Species::Squirrel)),
+                outline_mode: StrokeMode::new(Color::BLACK, 0.0),
+            },
+            Transform {
+                translation: Vec3::new(render_pos.x as f32, render_pos.y as f32, 0.0),
+                ..Default::default()
+            },
+        ))
+        .insert(Squirrel)
+        .insert(WalnutEater)
+        .insert(Species::Squirrel)
+        .insert(FoxPrey)
+        .insert(StrongBearPrey)
+        .insert(WeakBearPrey)
+        .insert(position)
+        .insert(Stamina::squirrel())
+        .insert(HP::squirrel())
+        .insert(Satiety::squirrel())
+        .id()
+}
+
+fn spawn_walnut(
+    commands: &mut Commands,
+    position: Position,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
     let shape = shapes::Circle {
         radius: (UNIT_WIDTH / 2) as f32,
         center: Vec2::new(0.0, 0.0),
@@ -530,34 +779,43 @@ fn spawn_walnut(commands: &mut Commands, position: Position, asset_server: &Res<
         ))
         .insert(Walnut)
         .insert(HumanPrey)
+        .insert(Species::Walnut)
         .insert(FoxPrey)
         .insert(position)
         .insert(Stamina::walnut())
         .insert(HP::walnut())
-        .insert(Satiety::walnut());
+        .insert(Satiety::walnut())
+        .id()
 }
 
 fn increase_walnut(
     mut commands: Commands,
-    timer: ResMut<GameTimer>,
+    stage: Res<Stage>,
+    difficulty: Res<Difficulty>,
     walnut_query: Query<&Position, With<Walnut>>,
     field_query: Query<&Position, With<Field>>,
     asset_server: Res<AssetServer>,
 ) {
-    if !timer.0.finished() {
-        return;
-    }
     let mut rng = rand::thread_rng();
 
     if walnut_query.iter().count() < MAX_WALNUT_COUNT
-        && rng.gen_range(1..=10000) <= PROBABILITY_INCREASE_WALNUT
+        && rng.gen_range(1..=10000) <= difficulty.walnut_rate(stage.level.walnut_rate)
     {
         for _ in 0..10 {
-            let random_grid = get_random_grid();
+            // 木があればその近くに、無ければ島のどこかに生える
+            let random_grid = match stage.level.walnut_sources.choose(&mut rng) {
+                Some(source) => source + &get_increase_pos(source, WALNUT_SOURCE_RANGE),
+                None => match get_random_grid(&stage.level, &mut rng) {
+                    Some(grid) => grid,
+                    None => return,
+                },
+            };
+            if !reachable(&field_query, random_grid.x, random_grid.y) {
+                continue;
+            }
             let random_pos = Position {
-                x: random_grid.x,
-                y: random_grid.y,
                 z: PLAYER_LAYER,
+                ..random_grid
             };
 
             let is_stacked = walnut_query.iter().any(|x| *x == random_pos);
@@ -571,44 +829,38 @@ fn increase_walnut(
 
 fn increase_fox(
     mut commands: Commands,
-    timer: ResMut<GameTimer>,
-    mut fox_query: Query<(&Position, &mut Satiety), With<Fox>>,
+    mut fox_query: Query<(&Position, &mut Satiety, &HP), With<Fox>>,
     field_query: Query<&Position, With<Field>>,
     asset_server: Res<AssetServer>,
 ) {
-    if !timer.0.finished() {
-        return;
-    }
-    fox_query.iter_mut().for_each(|(position, mut satiety)| {
-        if satiety.val >= satiety.max {
-            satiety.val -= satiety.max;
-            let offset = get_increase_pos(&position, 2);
-            let new_pos = Position {
-                x: position.x + offset.x,
-                y: position.y + offset.y,
-                z: position.z,
-            };
-            if reachable(&field_query, new_pos.x, new_pos.y) {
-                spawn_fox(&mut commands, new_pos, &asset_server);
+    fox_query
+        .iter_mut()
+        .for_each(|(position, mut satiety, hp)| {
+            if hp.val > 0.0 && satiety.val >= satiety.max {
+                satiety.val -= satiety.max;
+                let offset = get_increase_pos(&position, 2);
+                let new_pos = Position {
+                    x: position.x + offset.x,
+                    y: position.y + offset.y,
+                    z: position.z,
+                };
+                if reachable(&field_query, new_pos.x, new_pos.y) {
+                    spawn_fox(&mut commands, new_pos, &asset_server);
+                }
             }
-        }
-    })
+        })
 }
 
 fn increase_strong_bear(
     mut commands: Commands,
-    timer: ResMut<GameTimer>,
-    mut strong_bear_query: Query<(&Position, &mut Satiety), With<StrongBear>>,
+    mut strong_bear_query: Query<(&Position, &mut Satiety, &HP), With<StrongBear>>,
     field_query: Query<&Position, With<Field>>,
     asset_server: Res<AssetServer>,
 ) {
-    if !timer.0.finished() {
-        return;
-    }
     strong_bear_query
         .iter_mut()
-        .for_each(|(position, mut satiety)| {
-            if satiety.val >= satiety.max {
+        .for_each(|(position, mut satiety, hp)| {
+            if hp.val > 0.0 && satiety.val >= satiety.max {
                 satiety.val -= satiety.max;
                 let offset = get_increase_pos(&position, 2);
                 let new_pos = Position {
@@ -677,43 +929,42 @@ where
     }
 }
 fn move_fox(
-    timer: ResMut<GameTimer>,
     field_query: Query<&Position, With<Field>>,
     mut fox_query: Query<
-        (&mut Position, &mut Stamina),
-        (With<Fox>, Without<Field>, Without<FoxPrey>),
+        (&mut Position, &mut Stamina, &HP),
+        (With<Fox>, Without<Field>, Without<FoxPrey>, Without<Player>),
     >,
     prey_query: Query<&Position, With<FoxPrey>>,
 ) {
-    if !timer.0.finished() {
-        return;
-    }
-    fox_query.iter_mut().for_each(|(mut pos_fox, mut stamina)| {
-        if stamina.can_move() {
-            let decided_pos = approach(prey_query.iter(), &pos_fox, &field_query);
-            pos_fox.x = decided_pos.x;
-            pos_fox.y = decided_pos.y;
-            stamina.val = 0
-        }
-    })
+    fox_query
+        .iter_mut()
+        .for_each(|(mut pos_fox, mut stamina, hp)| {
+            if hp.val > 0.0 && stamina.can_move() {
+                let decided_pos = approach(prey_query.iter(), &pos_fox, &field_query);
+                pos_fox.x = decided_pos.x;
+                pos_fox.y = decided_pos.y;
+                stamina.val = 0
+            }
+        })
 }
 
 fn move_strong_bear(
-    timer: ResMut<GameTimer>,
     field_query: Query<&Position, With<Field>>,
     mut strong_bear_query: Query<
-        (&mut Position, &mut Stamina),
-        (With<StrongBear>, Without<Field>, Without<StrongBearPrey>),
+        (&mut Position, &mut Stamina, &HP),
+        (
+            With<StrongBear>,
+            Without<Field>,
+            Without<StrongBearPrey>,
+            Without<Player>,
+        ),
     >,
     prey_query: Query<&Position, With<StrongBearPrey>>,
 ) {
-    if !timer.0.finished() {
-        return;
-    }
     strong_bear_query
         .iter_mut()
-        .for_each(|(mut pos_strong_bear, mut stamina)| {
-            if stamina.can_move() {
+        .for_each(|(mut pos_strong_bear, mut stamina, hp)| {
+            if hp.val > 0.0 && stamina.can_move() {
                 let decided_pos = approach(prey_query.iter(), &pos_strong_bear, &field_query);
                 pos_strong_bear.x = decided_pos.x;
                 pos_strong_bear.y = decided_pos.y;
@@ -723,21 +974,22 @@ fn move_strong_bear(
 }
 
 fn move_weak_bear(
-    timer: ResMut<GameTimer>,
     field_query: Query<&Position, With<Field>>,
     mut weak_bear_query: Query<
-        (&mut Position, &mut Stamina),
-        (With<WeakBear>, Without<Field>, Without<WeakBearPrey>),
+        (&mut Position, &mut Stamina, &HP),
+        (
+            With<WeakBear>,
+            Without<Field>,
+            Without<WeakBearPrey>,
+            Without<Player>,
+        ),
     >,
     prey_query: Query<&Position, With<WeakBearPrey>>,
 ) {
-    if !timer.0.finished() {
-        return;
-    }
     weak_bear_query
         .iter_mut()
-        .for_each(|(mut pos_weak_bear, mut stamina)| {
-            if stamina.can_move() {
+        .for_each(|(mut pos_weak_bear, mut stamina, hp)| {
+            if hp.val > 0.0 && stamina.can_move() {
                 let decided_pos = approach(prey_query.iter(), &pos_weak_bear, &field_query);
                 pos_weak_bear.x = decided_pos.x;
                 pos_weak_bear.y = decided_pos.y;
@@ -747,215 +999,183 @@ fn move_weak_bear(
 }
 
 fn move_player(
-    key_input: Res<Input<KeyCode>>,
-    timer: ResMut<GameTimer>,
+    mut commands: Commands,
+    mut actions: ResMut<Actions>,
     field_query: Query<&Position, With<Field>>,
-    mut player_query: Query<(&mut Position, &mut Stamina), (With<Player>, Without<Field>)>,
+    mut player_query: Query<
+        (
+            Entity,
+            &Player,
+            &mut Position,
+            &mut Stamina,
+            &HP,
+            Option<&MoveTarget>,
+            Option<&mut RemoteInput>,
+        ),
+        Without<Field>,
+    >,
 ) {
-    if !timer.0.finished() {
-        return;
-    }
-
-    let mut x = 0;
-    let mut y = 0;
-    if key_input.pressed(KeyCode::Left) {
-        x -= 1;
-    }
-    if key_input.pressed(KeyCode::Right) {
-        x += 1;
-    }
-    if key_input.pressed(KeyCode::Up) {
-        y += 1;
-    }
-    if key_input.pressed(KeyCode::Down) {
-        y -= 1;
-    }
-
-    player_query
-        .iter_mut()
-        .for_each(|(mut pos_player, mut stamina)| {
-            if stamina.can_move() && (x != 0 || y != 0) {
-                if reachable(&field_query, pos_player.x + x, pos_player.y + y) {
-                    pos_player.x += x;
-                    pos_player.y += y;
-                    stamina.val = 0
+    player_query.iter_mut().for_each(
+        |(player, id, mut pos_player, mut stamina, hp, target, remote)| {
+            if hp.val <= 0.0 || !stamina.can_move() {
+                return;
+            }
+            // サーバーではクライアントから届いた入力で動かす
+            let (x, y) = match remote {
+                Some(mut remote) => remote.take(),
+                None => actions.take_move(id.id),
+            };
+            // キーで動かしたらタップした先へ歩くのをやめる
+            let (x, y) = if x != 0 || y != 0 {
+                if target.is_some() {
+                    commands.entity(player).remove::<MoveTarget>();
+                }
+                (x, y)
+            } else if let Some(target) = target {
+                let fields = field_query.iter().map(|pos| (pos.x, pos.y)).collect();
+                match next_step(&fields, &pos_player, &target.0) {
+                    Some(step) => step,
+                    None => {
+                        commands.entity(player).remove::<MoveTarget>();
+                        return;
+                    }
                 }
+            } else {
+                return;
+            };
+            if reachable(&field_query, pos_player.x + x, pos_player.y + y) {
+                pos_player.x += x;
+                pos_player.y += y;
+                stamina.val = 0
             }
-        })
+        },
+    )
 }
 
-fn heal(timer: ResMut<GameTimer>, mut query: Query<&mut Stamina>) {
-    if !timer.0.finished() {
-        return;
-    }
-
+fn heal(mut query: Query<&mut Stamina>) {
     query.iter_mut().for_each(|mut stamina| stamina.cool_down())
 }
 
-fn get_old(timer: ResMut<GameTimer>, mut query: Query<&mut Age>) {
-    if !timer.0.finished() {
-        return;
-    }
-
+fn get_old(mut query: Query<&mut Age>) {
     query.iter_mut().for_each(|mut age| age.val += 1)
 }
 
 fn goal(
     mut commands: Commands,
-    player_query: Query<(Entity, &Position), With<Player>>,
-    ship_query: Query<&Position, With<Ship>>,
-    mut state_query: Query<&mut State>,
-) {
-    player_query.iter().for_each(|(player, pos_player)| {
-        if ship_query
-            .iter()
-            .any(|pos_ship| pos_player.x == pos_ship.x && pos_player.y == pos_ship.y)
-        {
-            state_query.iter_mut().for_each(|mut state| {
-                state.kind = StateKind::GameClear;
-            });
-            commands.entity(player).despawn();
-        }
-    })
-}
-
-fn despawn_hp_text(mut commands: Commands, text_query: Query<Entity, With<HpText>>) {
-    text_query.iter().for_each(|text| {
-        commands.entity(text).despawn();
-    })
-}
-
-fn spawn_all_hp_text(
-    mut commands: Commands,
-    character_query: Query<(&Position, &HP)>,
+    clock: Res<GameClock>,
+    record: Res<Record>,
+    mut escaped: ResMut<Escaped>,
     asset_server: Res<AssetServer>,
+    player_query: Query<(Entity, &Player, &Position, &HP, Option<&Tools>)>,
+    ship_query: Query<(&Ship, &Position)>,
+    strong_bear_query: Query<&StrongBear>,
+    weak_bear_query: Query<&WeakBear>,
+    fox_query: Query<&Fox>,
+    walnut_query: Query<&Walnut>,
+    mut state_query: Query<&mut State>,
+    mode: Res<GameMode>,
+    character: Res<Character>,
+    mission: Res<Mission>,
 ) {
-    character_query
+    // Observer にはクリアもゲームオーバーも無い
+    if *mode == GameMode::Observer {
+        return;
+    }
+    // もう勝ち負けが決まっている
+    if state_query
         .iter()
-        .for_each(|(pos_character, hp_character)| {
-            spawn_hp_text(&mut commands, pos_character, hp_character, &asset_server);
-        })
-}
-
-fn spawn_hp_text(
-    commands: &mut Commands,
-    position: &Position,
-    hp: &HP,
-    asset_server: &Res<AssetServer>,
-) {
-    let text = Text::with_section(
-        format!("{}", hp.val as i32),
-        TextStyle {
-            font_size: 10.0,
-            color: Color::BLACK,
-            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-        },
-        Default::default(),
-    );
-
-    let origin_x = UNIT_WIDTH as i32 / 2;
-    let origin_y = UNIT_HEIGHT as i32 / 2;
-
-    commands
-        .spawn_bundle(TextBundle {
-            text: text,
-            style: Style {
-                position_type: PositionType::Absolute,
-                position: Rect {
-                    left: Val::Px((origin_x + position.x * UNIT_WIDTH as i32) as f32),
-                    bottom: Val::Px((origin_y + position.y * UNIT_HEIGHT as i32) as f32),
-                    ..default()
-                },
-                ..default()
-            },
-            ..Default::default()
-        })
-        .insert(HpText);
-}
-
-fn spawn_all_satiety_text(
-    mut commands: Commands,
-    character_query: Query<(&Position, &Satiety)>,
-    asset_server: Res<AssetServer>,
-) {
-    character_query
+        .any(|state| state.kind != StateKind::Playing)
+    {
+        return;
+    }
+    // 船に乗れるのは Terminal に停泊している間だけ。船で出るミッションでなければ乗らない
+    // Raft を作って漕ぎ出した人も船に乗ったのと同じ
+    let boarded: Vec<(Entity, usize, f32)> = player_query
         .iter()
-        .for_each(|(pos_character, satiety_character)| {
-            spawn_satiety_text(
-                &mut commands,
-                pos_character,
-                satiety_character,
-                &asset_server,
-            );
+        .filter(|(_, _, pos_player, hp, tools)| {
+            mission.needs_ship()
+                && hp.val > 0.0
+                && (tools.map_or(false, |tools| tools.afloat)
+                    || ship_query
+                        .iter()
+                        .any(|(ship, pos_ship)| ship.docked && *pos_player == pos_ship))
         })
-}
-
-fn spawn_satiety_text(
-    commands: &mut Commands,
-    position: &Position,
-    satiety: &Satiety,
-    asset_server: &Res<AssetServer>,
-) {
-    let text = Text::with_section(
-        format!("{}", satiety.val as i32),
-        TextStyle {
-            font_size: 10.0,
-            color: Color::BLACK,
-            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-        },
-        Default::default(),
-    );
+        .map(|(player, id, _, hp, _)| (player, id.id, hp.val))
+        .collect();
+    boarded.iter().for_each(|(player, id, hp)| {
+        commands.entity(*player).despawn_recursive();
+        escaped.0.push((*id, *hp));
+    });
+    // サーバーは乗った人を島から消すだけで、ずっと島を動かし続ける
+    if *mode == GameMode::Server {
+        return;
+    }
+    let mut finish = |kind: StateKind| {
+        state_query.iter_mut().for_each(|mut state| {
+            state.kind = kind;
+        });
+    };
 
-    let origin_x = UNIT_WIDTH as i32 / 2;
-    let origin_y = UNIT_HEIGHT as i32 / 2;
+    // Versus は先に乗った方の勝ち。残った方は島に取り残される
+    if *mode == GameMode::Versus {
+        if let Some((_, id, _)) = boarded.first() {
+            finish(StateKind::Won(*id));
+            return;
+        }
+    }
+    let population = Population {
+        strong_bear: strong_bear_query.iter().count() as i32,
+        weak_bear: weak_bear_query.iter().count() as i32,
+        fox: fox_query.iter().count() as i32,
+        walnut: walnut_query.iter().count() as i32,
+    };
+    // まだ島に生きている Player の HP
+    let left: Vec<f32> = player_query
+        .iter()
+        .filter(|(player, _, _, hp, _)| hp.val > 0.0 && !boarded.iter().any(|b| b.0 == *player))
+        .map(|(_, _, _, hp, _)| hp.val)
+        .collect();
+    // 守るはずの数を割ったら、島にいてもいなくても失敗
+    if mission.failed() {
+        finish(StateKind::GameOver);
+        return;
+    }
+    // 船で出なくてよいミッションは、島に生き残ったまま満たせばクリア
+    let reached = !left.is_empty() && !mission.needs_ship() && mission.complete(false);
+    if !left.is_empty() && !reached {
+        return;
+    }
+    let cleared =
+        reached || (!escaped.0.is_empty() && *mode != GameMode::Versus && mission.complete(true));
+    if !cleared {
+        finish(StateKind::GameOver);
+        return;
+    }
+    finish(StateKind::GameClear);
 
-    commands
-        .spawn_bundle(TextBundle {
-            text: text,
-            style: Style {
-                position_type: PositionType::Absolute,
-                position: Rect {
-                    left: Val::Px((origin_x + position.x * UNIT_WIDTH as i32) as f32),
-                    bottom: Val::Px((origin_y + position.y * UNIT_HEIGHT as i32) as f32),
-                    ..default()
-                },
-                ..default()
-            },
-            ..Default::default()
-        })
-        .insert(HpText);
+    // Coop は島を出た人 (船で出ないミッションでは生き残った人) の HP の平均で数える
+    let hps: Vec<f32> = if reached {
+        left
+    } else {
+        escaped.0.iter().map(|(_, hp)| *hp).collect()
+    };
+    let hp = hps.iter().sum::<f32>() / hps.len() as f32;
+    let score = Score::new(clock.tick, hp, &record, &population);
+    spawn_result_text(&mut commands, &score, &record, &asset_server);
+    // ハイスコアは 1 人で人間を遊んだときだけ
+    if *mode == GameMode::Normal && character.species(*mode) == Species::Human {
+        commands.insert_resource(score);
+    }
 }
 
 fn despawn(mut commands: Commands, mut food_query: Query<(Entity, &HP)>) {
     food_query.iter_mut().for_each(|(entity, hp)| {
         if hp.val <= 0.0 {
-            commands.entity(entity).despawn();
+            commands.entity(entity).despawn_recursive();
         }
     })
 }
 
-fn move_ship(
-    timer: ResMut<GameTimer>,
-    mut ship_query: Query<(&mut Ship, &mut Position, &mut Stamina)>,
-) {
-    if !timer.0.finished() {
-        return;
-    }
-
-    ship_query
-        .iter_mut()
-        .for_each(|(mut ship, mut pos, mut stamina)| {
-            if stamina.can_move() {
-                let offset = SHIP_MOVING[ship.index];
-
-                pos.x += offset.x;
-                pos.y += offset.y;
-                stamina.val = 0;
-                ship.index = (ship.index + 1) % SHIP_MOVING.len()
-            }
-        })
-}
-
 fn weaken_bear(
     mut commands: Commands,
     mut strong_bear_query: Query<(Entity, &Position, &HP, &Age), With<StrongBear>>,
@@ -964,20 +1184,19 @@ fn weaken_bear(
     strong_bear_query
         .iter_mut()
         .for_each(|(strong_bear, pos, hp, age)| {
-            if age.val >= HEALTHSPAN_STRONG_BEAR {
-                commands.entity(strong_bear).despawn();
+            // 死んだ熊は despawn に任せる
+            if hp.val > 0.0 && age.val >= HEALTHSPAN_STRONG_BEAR {
+                commands.entity(strong_bear).despawn_recursive();
                 spawn_weak_bear(&mut commands, *pos, &asset_server, hp.val);
             }
         });
 }
 
-fn die_of_old_age(
-    mut commands: Commands,
-    mut weak_bear_query: Query<(Entity, &Age), With<WeakBear>>,
-) {
-    weak_bear_query.iter_mut().for_each(|(weak_bear, age)| {
+// 寿命が来たら HP を 0 にして、Death でまとめて despawn する
+fn die_of_old_age(mut weak_bear_query: Query<(&Age, &mut HP), With<WeakBear>>) {
+    weak_bear_query.iter_mut().for_each(|(age, mut hp)| {
         if age.val >= LIFESPAN_WEAK_BEAR {
-            commands.entity(weak_bear).despawn();
+            hp.val = 0.0;
         }
     });
 }
@@ -985,16 +1204,57 @@ fn die_of_old_age(
 fn restart(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut timetable: ResMut<Timetable>,
+    stage: Res<Stage>,
+    mode: Res<GameMode>,
+    character: Res<Character>,
+    difficulty: Res<Difficulty>,
+    mut screen: ResMut<Screen>,
+    mut clock: ResMut<GameClock>,
+    mut record: ResMut<Record>,
+    mut escaped: ResMut<Escaped>,
     mut q: Query<Entity>,
-    key: Res<Input<KeyCode>>,
+    mut actions: ResMut<Actions>,
+    mut start_events: EventReader<StartGame>,
 ) {
-    for k in key.get_just_pressed() {
-        if *k == KeyCode::R {
-            q.iter_mut()
-                .for_each(|entity| commands.entity(entity).despawn());
-            commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-            commands.spawn_bundle(UiCameraBundle::default());
-            initial_spawn(&mut commands, &asset_server);
-        }
+    // 遊んでいる間だけやり直せる。通信対戦の島はサーバーのもの
+    let restart_key = *screen == Screen::Playing
+        && *mode != GameMode::Online
+        && actions.just_pressed(Action::Restart);
+    if start_events.iter().count() > 0 || restart_key {
+        q.iter_mut()
+            .for_each(|entity| commands.entity(entity).despawn());
+        spawn_cameras(&mut commands);
+        clock.tick = 0;
+        *record = Record::default();
+        *escaped = Escaped::default();
+        commands.remove_resource::<Score>();
+        // Versus は先に船に乗る競争、通信対戦は船で島を出るだけなので Level の objective は使わない
+        commands.insert_resource(match *mode {
+            GameMode::Observer => Mission::default(),
+            GameMode::Versus | GameMode::Online | GameMode::Server => {
+                Mission::new(&[], Objective::Escape)
+            }
+            _ => Mission::new(
+                &stage.level.objectives,
+                default_objective(character.species(*mode)),
+            ),
+        });
+        *screen = Screen::Playing;
+        actions.clear_buffer();
+        // 通信対戦の船はサーバーと同じ時刻表で動かす
+        timetable.lines = match *mode {
+            GameMode::Online | GameMode::Server => stage.level.lines.clone(),
+            _ => difficulty.ship_lines(&stage.level.lines),
+        };
+        initial_spawn(
+            &mut commands,
+            &asset_server,
+            &timetable,
+            &stage,
+            *mode,
+            *character,
+            *difficulty,
+        );
     }
 }
@@ -0,0 +1,287 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::hud::hud_left;
+use crate::score::Record;
+use crate::ship::*;
+use crate::simulation::*;
+use crate::GameClock;
+
+use bevy::prelude::*;
+
+pub struct MissionPlugin;
+
+impl Plugin for MissionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Mission::default())
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Goal).with_system(track_objectives.label("objectives")),
+            )
+            .add_system(mission_text);
+    }
+}
+
+/*
+ * Objective: Level に書くクリア条件
+ *   objective escape                   船か Raft で島を出る
+ *   objective survive <sec>            生き延びる
+ *   objective ship <n>                 n 隻目の船が来るまで生き延びる
+ *   objective eat <species> <count>    Player が食べる (walnut, fox, weak_bear)
+ *   objective reach <species> <count>  島のこの種類をここまで増やす
+ *   objective keep <species> <count>   島のこの種類を最後までこれ以上に保つ
+ * すべて満たせばクリア、keep を一度でも割ったらゲームオーバー
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Objective {
+    Escape,
+    // tick
+    Survive(i32),
+    Ship(i32),
+    Eat(Species, i32),
+    Reach(Species, i32),
+    Keep(Species, i32),
+}
+
+impl Objective {
+    pub fn parse(cols: &[&str]) -> Result<Objective, String> {
+        let num = |i: usize| -> Result<i32, String> {
+            cols.get(i)
+                .ok_or(format!("missing column {}", i))?
+                .parse::<i32>()
+                .map_err(|e| e.to_string())
+        };
+        let species = |i: usize| -> Result<Species, String> {
+            let name = cols.get(i).ok_or(format!("missing column {}", i))?;
+            Species::from_name(name).ok_or(format!("unknown species {}", name))
+        };
+        let kind = cols.get(0).ok_or("missing objective")?;
+        Ok(match *kind {
+            "escape" => Objective::Escape,
+            "survive" => Objective::Survive(num(1)? * GAME_DEFAULT_FPS),
+            "ship" => Objective::Ship(num(1)?),
+            "eat" => match species(1)? {
+                // 数えていない種類は食べても達成できない
+                eaten if Record::tracks(eaten) => Objective::Eat(eaten, num(2)?),
+                eaten => return Err(format!("can't eat {}", eaten.name())),
+            },
+            "reach" => Objective::Reach(species(1)?, num(2)?),
+            "keep" => Objective::Keep(species(1)?, num(2)?),
+            other => return Err(format!("unknown objective {}", other)),
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            Objective::Escape => "escape".to_string(),
+            Objective::Survive(ticks) => format!("survive {}", ticks / GAME_DEFAULT_FPS),
+            Objective::Ship(n) => format!("ship {}", n),
+            Objective::Eat(species, n) => format!("eat {} {}", species.name(), n),
+            Objective::Reach(species, n) => format!("reach {} {}", species.name(), n),
+            Objective::Keep(species, n) => format!("keep {} {}", species.name(), n),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::Escape => "escape the island".to_string(),
+            Objective::Survive(ticks) => format!("survive {}s", ticks / GAME_DEFAULT_FPS),
+            Objective::Ship(n) => format!("survive until ship #{}", n),
+            Objective::Eat(species, n) => format!("eat {} {}", n, species.name()),
+            Objective::Reach(species, n) => format!("grow {} to {}", species.name(), n),
+            Objective::Keep(species, n) if *n <= 1 => {
+                format!("don't let {} go extinct", species.name())
+            }
+            Objective::Keep(species, n) => format!("keep {}+ {}", n, species.name()),
+        }
+    }
+
+    // 達成するまで続く条件。keep は守り続けるだけなので、これだけではクリアにならない
+    fn finishes(&self) -> bool {
+        !matches!(self, Objective::Keep(..))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Progress {
+    Doing,
+    Done,
+    Failed,
+}
+
+/// いま遊んでいる島のクリア条件と、その進み具合
+#[derive(Default)]
+pub struct Mission {
+    pub objectives: Vec<Objective>,
+    pub progress: Vec<Progress>,
+    // 表示用の今の値 (食べた数、島の数など)
+    current: Vec<i32>,
+}
+
+impl Mission {
+    /// Level に書いてある条件を使う。終わりの無い条件しか無ければ、生き物ごとの条件を足す
+    pub fn new(objectives: &[Objective], goal: Objective) -> Mission {
+        let mut objectives = objectives.to_vec();
+        if !objectives.iter().any(Objective::finishes) {
+            objectives.push(goal);
+        }
+        Mission {
+            progress: vec![Progress::Doing; objectives.len()],
+            current: vec![0; objectives.len()],
+            objectives,
+        }
+    }
+    pub fn needs_ship(&self) -> bool {
+        self.objectives.contains(&Objective::Escape)
+    }
+    pub fn failed(&self) -> bool {
+        self.progress.contains(&Progress::Failed)
+    }
+    /// 船で島を出たかどうかも合わせて、すべて満たしたか
+    pub fn complete(&self, escaped: bool) -> bool {
+        self.objectives
+            .iter()
+            .zip(&self.progress)
+            .all(|(objective, progress)| match objective {
+                Objective::Escape => escaped,
+                Objective::Keep(..) => *progress != Progress::Failed,
+                _ => *progress == Progress::Done,
+            })
+    }
+}
+
+// n 隻目の船が着く tick
+fn nth_arrival(lines: &[ShipLine], n: i32) -> Option<i32> {
+    let n = n.max(1);
+    let mut arrivals: Vec<i32> = lines
+        .iter()
+        .flat_map(|line| (0..n).map(move |k| line.first_arrival + k * line.interval))
+        .collect();
+    arrivals.sort_unstable();
+    arrivals.get(n as usize - 1).copied()
+}
+
+fn track_objectives(
+    clock: Res<GameClock>,
+    record: Res<Record>,
+    timetable: Res<Timetable>,
+    mut mission: ResMut<Mission>,
+    creature_query: Query<(&Species, &HP)>,
+) {
+    // 食べられたものは tick の最後に消えるので数えない
+    let count = |target: Species| {
+        creature_query
+            .iter()
+            .filter(|(species, hp)| **species == target && hp.val > 0.0)
+            .count() as i32
+    };
+    let mission = &mut *mission;
+    mission
+        .objectives
+        .iter()
+        .zip(mission.progress.iter_mut())
+        .zip(mission.current.iter_mut())
+        .for_each(|((objective, progress), current)| {
+            // 達成したものと失敗したものはそのまま
+            if *progress != Progress::Doing {
+                return;
+            }
+            let done = match objective {
+                // 船に乗ったかは goal が見る
+                Objective::Escape => false,
+                Objective::Survive(ticks) => {
+                    *current = clock.tick / GAME_DEFAULT_FPS;
+                    clock.tick >= *ticks
+                }
+                Objective::Ship(n) => {
+                    *current = (1..=*n)
+                        .take_while(|k| {
+                            nth_arrival(&timetable.lines, *k).map_or(false, |t| clock.tick >= t)
+                        })
+                        .count() as i32;
+                    *current >= *n
+                }
+                Objective::Eat(species, n) => {
+                    *current = record.count(*species);
+                    *current >= *n
+                }
+                Objective::Reach(species, n) => {
+                    *current = count(*species);
+                    *current >= *n
+                }
+                Objective::Keep(species, n) => {
+                    *current = count(*species);
+                    if *current < *n {
+                        *progress = Progress::Failed;
+                    }
+                    false
+                }
+            };
+            if done {
+                *progress = Progress::Done;
+            }
+        });
+}
+
+#[derive(Component)]
+pub struct MissionText;
+
+// 画面右下、Field の外に並べる
+pub fn spawn_mission_text(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(hud_left()),
+                    bottom: Val::Px(UNIT_HEIGHT as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(MissionText);
+}
+
+fn mission_text(mission: Res<Mission>, mut text_query: Query<&mut Text, With<MissionText>>) {
+    let value = mission
+        .objectives
+        .iter()
+        .zip(&mission.progress)
+        .zip(&mission.current)
+        .map(|((objective, progress), current)| {
+            let mark = match progress {
+                Progress::Doing => "[ ]",
+                Progress::Done => "[x]",
+                Progress::Failed => "[!]",
+            };
+            let count = match objective {
+                Objective::Escape => String::new(),
+                Objective::Survive(ticks) => {
+                    format!(" ({}/{}s)", current, ticks / GAME_DEFAULT_FPS)
+                }
+                Objective::Ship(n) | Objective::Eat(_, n) | Objective::Reach(_, n) => {
+                    format!(" ({}/{})", current, n)
+                }
+                Objective::Keep(..) => format!(" ({})", current),
+            };
+            format!("{} {}{}", mark, objective.describe(), count)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    text_query.iter_mut().for_each(|mut text| {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    });
+}
@@ -0,0 +1,589 @@
+use crate::camera::spawn_cameras;
+use crate::components::*;
+use crate::consts::*;
+use crate::input::*;
+use crate::level::*;
+use crate::ship::move_ship;
+use crate::simulation::*;
+use crate::storage;
+use crate::title::{spawn_title, Screen};
+use crate::tween::update_tween;
+use crate::{spawn_player, spawn_species, GameClock, GameMode, Stage, StartGame};
+
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub struct NetPlugin;
+
+/*
+ * 通信対戦のクライアント
+ * サーバー (cargo run -- --server) が島を動かし、tick ごとに変わったところだけを送ってくる
+ * 自分の移動は返事を待たずに動かして送り、サーバーの ack で答え合わせをする
+ * メッセージは 1 行に 1 つ
+ *   サーバー -> クライアント
+ *     level <Level のテキストの 1 行>
+ *     welcome <player>               level を送り終えた。あなたは player 番
+ *     tick <n>
+ *     spawn <id> <species> <x> <y>
+ *     player <id> <player> <x> <y>
+ *     move <id> <x> <y>
+ *     hp <id> <hp>
+ *     despawn <id>
+ *     ack <seq>                      seq 番までの入力を反映した
+ *   クライアント -> サーバー
+ *     input <seq> <dx> <dy>
+ */
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(NetClient::default())
+            .add_system_to_stage(CoreStage::PreUpdate, net_receive.after("actions"))
+            .add_system(net_input)
+            // サーバーの tick に合わせて船と描画を動かす
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(online)
+                    .with_system(move_ship)
+                    .with_system(update_tween),
+            );
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ServerMessage {
+    Level(String),
+    Welcome(usize),
+    Tick(i32),
+    Spawn(u64, Species, Position),
+    Player(u64, usize, Position),
+    Move(u64, Position),
+    Hp(u64, f32),
+    Despawn(u64),
+    Ack(u32),
+}
+
+impl ServerMessage {
+    pub fn to_line(&self) -> String {
+        match self {
+            ServerMessage::Level(line) => format!("level {}", line),
+            ServerMessage::Welcome(player) => format!("welcome {}", player),
+            ServerMessage::Tick(tick) => format!("tick {}", tick),
+            ServerMessage::Spawn(id, species, pos) => {
+                format!("spawn {} {} {} {}", id, species.name(), pos.x, pos.y)
+            }
+            ServerMessage::Player(id, player, pos) => {
+                format!("player {} {} {} {}", id, player, pos.x, pos.y)
+            }
+            ServerMessage::Move(id, pos) => format!("move {} {} {}", id, pos.x, pos.y),
+            ServerMessage::Hp(id, hp) => format!("hp {} {}", id, hp),
+            ServerMessage::Despawn(id) => format!("despawn {}", id),
+            ServerMessage::Ack(seq) => format!("ack {}", seq),
+        }
+    }
+    pub fn parse(line: &str) -> Option<ServerMessage> {
+        // level の行は空白も含めてそのまま
+        if let Some(rest) = line.strip_prefix("level") {
+            return Some(ServerMessage::Level(rest.trim_start().to_string()));
+        }
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| -> Option<i64> { cols.get(i)?.parse().ok() };
+        let id = || Some(num(1)? as u64);
+        let pos = |i: usize| Some(grid(num(i)? as i32, num(i + 1)? as i32));
+        let message = match *cols.get(0)? {
+            "welcome" => ServerMessage::Welcome(num(1)? as usize),
+            "tick" => ServerMessage::Tick(num(1)? as i32),
+            "spawn" => ServerMessage::Spawn(id()?, Species::from_name(cols.get(2)?)?, pos(3)?),
+            "player" => ServerMessage::Player(id()?, num(2)? as usize, pos(3)?),
+            "move" => ServerMessage::Move(id()?, pos(2)?),
+            "hp" => ServerMessage::Hp(id()?, cols.get(2)?.parse().ok()?),
+            "despawn" => ServerMessage::Despawn(id()?),
+            "ack" => ServerMessage::Ack(num(1)? as u32),
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientMessage {
+    Input(u32, (i32, i32)),
+}
+
+impl ClientMessage {
+    pub fn to_line(&self) -> String {
+        match self {
+            ClientMessage::Input(seq, (x, y)) => format!("input {} {} {}", seq, x, y),
+        }
+    }
+    pub fn parse(line: &str) -> Option<ClientMessage> {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| -> Option<i32> { cols.get(i)?.parse().ok() };
+        match *cols.get(0)? {
+            "input" => {
+                let seq = cols.get(1)?.parse().ok()?;
+                // 1 回に動けるのは隣のマスまで
+                let direction = (num(2)?.clamp(-1, 1), num(3)?.clamp(-1, 1));
+                Some(ClientMessage::Input(seq, direction))
+            }
+            _ => None,
+        }
+    }
+}
+
+type Lines = Arc<Mutex<VecDeque<String>>>;
+
+/// WebSocket の接続。やりとりは別のスレッド (wasm ではブラウザ) に任せ、ここでは行を受け渡すだけ
+#[derive(Clone, Default)]
+pub struct Connection {
+    incoming: Lines,
+    outgoing: Lines,
+    closed: Arc<AtomicBool>,
+}
+
+impl Connection {
+    pub fn send(&self, line: String) {
+        self.outgoing.lock().unwrap().push_back(line);
+    }
+    pub fn receive(&self) -> Option<String> {
+        self.incoming.lock().unwrap().pop_front()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+// 送る行はまとめて 1 つのメッセージにする
+fn take_outgoing(connection: &Connection) -> Option<String> {
+    let mut outgoing = connection.outgoing.lock().unwrap();
+    if outgoing.is_empty() {
+        return None;
+    }
+    Some(outgoing.drain(..).collect::<Vec<_>>().join("\n"))
+}
+
+fn push_incoming(connection: &Connection, text: &str) {
+    connection
+        .incoming
+        .lock()
+        .unwrap()
+        .extend(text.lines().map(String::from));
+}
+
+/// 切れるまで送ったり受け取ったりを繰り返す。socket はノンブロッキングにしておくこと
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pump<S: std::io::Read + std::io::Write>(
+    socket: &mut tungstenite::WebSocket<S>,
+    connection: &Connection,
+) {
+    use tungstenite::{Error, Message};
+    let would_block = |e: &Error| match e {
+        Error::Io(e) => e.kind() == std::io::ErrorKind::WouldBlock,
+        _ => false,
+    };
+    while !connection.is_closed() {
+        if let Some(text) = take_outgoing(connection) {
+            match socket.write_message(Message::Text(text)) {
+                Ok(()) => {}
+                // 送りきれなかった分は write_pending で送る
+                Err(e) if would_block(&e) => {}
+                Err(_) => break,
+            }
+        }
+        match socket.read_message() {
+            Ok(Message::Text(text)) => push_incoming(connection, &text),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(e) if would_block(&e) => {
+                let _ = socket.write_pending();
+                std::thread::sleep(std::time::Duration::from_millis(NET_POLL_MS));
+            }
+            Err(_) => break,
+        }
+    }
+    connection.close();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn connect(url: &str) -> Connection {
+    use tungstenite::stream::MaybeTlsStream;
+    let connection = Connection::default();
+    let shared = connection.clone();
+    let url = url.to_string();
+    std::thread::spawn(move || {
+        let mut socket = match tungstenite::connect(url.as_str()) {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                warn!("failed to connect {}: {}", url, e);
+                shared.close();
+                return;
+            }
+        };
+        if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+            if stream.set_nonblocking(true).is_err() {
+                shared.close();
+                return;
+            }
+        }
+        pump(&mut socket, &shared);
+    });
+    connection
+}
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static SOCKET: std::cell::RefCell<Option<web_sys::WebSocket>> = std::cell::RefCell::new(None);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn connect(url: &str) -> Connection {
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+    let connection = Connection::default();
+    let socket = match web_sys::WebSocket::new(url) {
+        Ok(socket) => socket,
+        Err(_) => {
+            warn!("failed to connect {}", url);
+            connection.close();
+            return connection;
+        }
+    };
+    let shared = connection.clone();
+    let on_message = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+        if let Some(text) = event.data().as_string() {
+            push_incoming(&shared, &text);
+        }
+    }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+    let shared = connection.clone();
+    let on_close = Closure::wrap(
+        Box::new(move |_: web_sys::Event| shared.close()) as Box<dyn FnMut(web_sys::Event)>
+    );
+    socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    on_close.forget();
+    SOCKET.with(|cell| *cell.borrow_mut() = Some(socket));
+    connection
+}
+
+// wasm では別のスレッドが無いので、開いていれば毎フレーム送る
+#[cfg(target_arch = "wasm32")]
+fn flush(connection: &Connection) {
+    SOCKET.with(|cell| {
+        if let Some(socket) = cell.borrow().as_ref() {
+            if socket.ready_state() != web_sys::WebSocket::OPEN {
+                return;
+            }
+            if let Some(text) = take_outgoing(connection) {
+                if socket.send_with_str(&text).is_err() {
+                    connection.close();
+                }
+            }
+        }
+    });
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn flush(_connection: &Connection) {}
+
+pub fn server_url() -> String {
+    storage::read(NET_SERVER_FILE)
+        .map(|text| text.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| NET_DEFAULT_URL.to_string())
+}
+
+/// サーバーで、クライアントから届いた移動の入力
+#[derive(Component, Default)]
+pub struct RemoteInput {
+    queue: VecDeque<(u32, (i32, i32))>,
+    /// 最後に使った入力の番号
+    pub acked: u32,
+}
+
+impl RemoteInput {
+    pub fn push(&mut self, seq: u32, direction: (i32, i32)) {
+        if self.queue.len() > INPUT_BUFFER_LEN {
+            self.queue.pop_front();
+        }
+        self.queue.push_back((seq, direction));
+    }
+    pub fn take(&mut self) -> (i32, i32) {
+        match self.queue.pop_front() {
+            Some((seq, direction)) => {
+                self.acked = seq;
+                direction
+            }
+            None => (0, 0),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct NetClient {
+    level: Vec<String>,
+    player: Option<usize>,
+    entities: HashMap<u64, Entity>,
+    // 自分の Player の id と、サーバーが最後に決めた位置
+    me: Option<u64>,
+    confirmed: Option<Position>,
+    seq: u32,
+    // 送ったけれど、まだ ack が来ていない入力
+    pending: VecDeque<(u32, (i32, i32))>,
+    cooldown: f32,
+}
+
+fn online(mode: Res<GameMode>) -> ShouldRun {
+    if *mode == GameMode::Online {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn net_receive(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    connection: Option<Res<Connection>>,
+    mut client: ResMut<NetClient>,
+    mut stage: ResMut<Stage>,
+    mut mode: ResMut<GameMode>,
+    mut screen: ResMut<Screen>,
+    mut clock: ResMut<GameClock>,
+    mut sim: ResMut<Simulation>,
+    mut start_events: EventWriter<StartGame>,
+    entity_query: Query<Entity>,
+    mut mover_query: Query<(&mut Position, Option<&mut HP>), Without<Field>>,
+) {
+    let connection = match connection {
+        Some(connection) => connection,
+        None => return,
+    };
+    flush(&connection);
+
+    // 切れたらタイトルに戻る
+    if connection.is_closed() {
+        commands.remove_resource::<Connection>();
+        *client = NetClient::default();
+        sim.remote = false;
+        if *mode == GameMode::Online {
+            *mode = GameMode::Normal;
+            entity_query
+                .iter()
+                .for_each(|entity| commands.entity(entity).despawn());
+            spawn_cameras(&mut commands);
+            spawn_title(&mut commands, &asset_server);
+            *screen = Screen::Title;
+        }
+        return;
+    }
+
+    while let Some(line) = connection.receive() {
+        let message = match ServerMessage::parse(&line) {
+            Some(message) => message,
+            None => {
+                warn!("broken message from server: {}", line);
+                continue;
+            }
+        };
+        match message {
+            ServerMessage::Level(line) => client.level.push(line),
+            ServerMessage::Welcome(player) => {
+                let level = Level::parse(&client.level.join("\n"));
+                client.level.clear();
+                match level {
+                    Ok(level) => stage.level = level,
+                    Err(e) => {
+                        warn!("broken level from server: {}", e);
+                        connection.close();
+                        return;
+                    }
+                }
+                *client = NetClient {
+                    player: Some(player),
+                    ..NetClient::default()
+                };
+                *mode = GameMode::Online;
+                sim.remote = true;
+                start_events.send(StartGame);
+                // 残りは島を作り直してから置く
+                return;
+            }
+            ServerMessage::Tick(tick) => clock.tick = tick,
+            ServerMessage::Spawn(id, species, pos) => {
+                if let Some(entity) = spawn_species(&mut commands, species, pos, &asset_server) {
+                    client.entities.insert(id, entity);
+                }
+            }
+            ServerMessage::Player(id, player, pos) => {
+                let mine = client.player == Some(player);
+                // 自分だけが Player。ほかの人は 2P の色で描くだけ
+                let entity = spawn_player(
+                    &mut commands,
+                    Position {
+                        z: PLAYER_LAYER,
+                        ..pos
+                    },
+                    if mine { 0 } else { 1 },
+                    Species::Human,
+                    &asset_server,
+                );
+                if mine {
+                    client.me = Some(id);
+                    client.confirmed = Some(pos);
+                } else {
+                    commands.entity(entity).remove::<Player>();
+                }
+                client.entities.insert(id, entity);
+            }
+            ServerMessage::Move(id, pos) => {
+                if client.me == Some(id) {
+                    client.confirmed = Some(pos);
+                    continue;
+                }
+                if let Some(entity) = client.entities.get(&id) {
+                    if let Ok((mut current, _)) = mover_query.get_mut(*entity) {
+                        current.x = pos.x;
+                        current.y = pos.y;
+                    }
+                }
+            }
+            ServerMessage::Hp(id, hp) => {
+                if let Some(entity) = client.entities.get(&id) {
+                    if let Ok((_, Some(mut current))) = mover_query.get_mut(*entity) {
+                        current.val = hp;
+                    }
+                }
+            }
+            ServerMessage::Despawn(id) => {
+                if let Some(entity) = client.entities.remove(&id) {
+                    commands.entity(entity).despawn_recursive();
+                }
+                if client.me == Some(id) {
+                    client.me = None;
+                }
+            }
+            ServerMessage::Ack(seq) => {
+                client.pending.retain(|(pending, _)| *pending > seq);
+                reconcile(&client, &mut mover_query);
+            }
+        }
+    }
+}
+
+// サーバーが決めた位置から、まだ届いていない入力をやり直す
+fn reconcile(
+    client: &NetClient,
+    mover_query: &mut Query<(&mut Position, Option<&mut HP>), Without<Field>>,
+) {
+    let (me, confirmed) = match (client.me, client.confirmed) {
+        (Some(me), Some(confirmed)) => (me, confirmed),
+        _ => return,
+    };
+    let entity = match client.entities.get(&me) {
+        Some(entity) => *entity,
+        None => return,
+    };
+    if let Ok((mut pos, _)) = mover_query.get_mut(entity) {
+        let predicted = client
+            .pending
+            .iter()
+            .fold(confirmed, |pos, (_, (x, y))| grid(pos.x + x, pos.y + y));
+        pos.x = predicted.x;
+        pos.y = predicted.y;
+    }
+}
+
+// 人間が Stamina を溜めて次に動けるまでの秒数
+fn move_interval() -> f32 {
+    let ticks = (MAX_STAMINA + HEALING_STAMINA_HUMAN - 1) / HEALING_STAMINA_HUMAN;
+    (ticks as u64 * GAME_TICK) as f32 / 1000.0
+}
+
+fn net_input(
+    time: Res<Time>,
+    mode: Res<GameMode>,
+    connection: Option<Res<Connection>>,
+    mut client: ResMut<NetClient>,
+    mut actions: ResMut<Actions>,
+    field_query: Query<&Position, With<Field>>,
+    mut player_query: Query<&mut Position, (With<Player>, Without<Field>)>,
+) {
+    let connection = match connection {
+        Some(connection) if *mode == GameMode::Online => connection,
+        _ => return,
+    };
+    client.cooldown -= time.delta_seconds();
+    if client.cooldown > 0.0 {
+        return;
+    }
+    let mut pos = match player_query.get_single_mut() {
+        Ok(pos) => pos,
+        Err(_) => return,
+    };
+    let (x, y) = actions.take_move(0);
+    if (x, y) == (0, 0) {
+        return;
+    }
+    // 海に向かっては送らない
+    if !field_query
+        .iter()
+        .any(|field| field.x == pos.x + x && field.y == pos.y + y)
+    {
+        return;
+    }
+    client.seq += 1;
+    let seq = client.seq;
+    connection.send(ClientMessage::Input(seq, (x, y)).to_line());
+    client.pending.push_back((seq, (x, y)));
+    client.cooldown = move_interval();
+    // 返事を待たずに動かしておく
+    pos.x += x;
+    pos.y += y;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_through_lines() {
+        [
+            ServerMessage::Level("ship 3 1 10 100 20 R".to_string()),
+            ServerMessage::Welcome(1),
+            ServerMessage::Tick(42),
+            ServerMessage::Spawn(7, Species::WeakBear, grid(-2, 3)),
+            ServerMessage::Player(8, 1, grid(0, -1)),
+            ServerMessage::Move(7, grid(-1, 3)),
+            ServerMessage::Hp(8, 3.0),
+            ServerMessage::Despawn(7),
+            ServerMessage::Ack(12),
+        ]
+        .iter()
+        .for_each(|message| {
+            assert_eq!(
+                ServerMessage::parse(&message.to_line()).as_ref(),
+                Some(message)
+            );
+        });
+        let input = ClientMessage::Input(5, (-1, 1));
+        assert_eq!(ClientMessage::parse(&input.to_line()), Some(input));
+    }
+
+    #[test]
+    fn broken_messages_are_ignored() {
+        ["", "welcome", "move 1 2", "spawn 1 dragon 0 0", "hello 1"]
+            .iter()
+            .for_each(|line| assert_eq!(ServerMessage::parse(line), None, "{}", line));
+        // 1 回に動けるのは隣のマスまで
+        assert_eq!(
+            ClientMessage::parse("input 3 5 -9"),
+            Some(ClientMessage::Input(3, (1, -1)))
+        );
+        assert_eq!(ClientMessage::parse("input x 1 1"), None);
+    }
+}
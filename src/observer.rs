@@ -0,0 +1,107 @@
+use crate::camera::{cursor_grid, CameraQuery};
+use crate::components::*;
+use crate::consts::*;
+use crate::{reachable, spawn_species, GameMode};
+
+use bevy::prelude::*;
+
+pub struct ObserverPlugin;
+
+/*
+ * Observer: Player を置かずに生態系だけを眺めるモード
+ * 1~4 で置くものを選び、クリックしたマスに置く
+ */
+impl Plugin for ObserverPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Brush(Species::Walnut))
+            .add_system(choose_brush)
+            .add_system(place_creature)
+            .add_system(brush_text);
+    }
+}
+
+/// クリックで置く生き物
+pub struct Brush(pub Species);
+
+#[derive(Component)]
+pub struct BrushText;
+
+pub fn spawn_brush_text(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px((FIELD_LEFTBTM_X * UNIT_WIDTH as i32) as f32),
+                    bottom: Val::Px(UNIT_HEIGHT as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(BrushText);
+}
+
+fn choose_brush(mode: Res<GameMode>, key: Res<Input<KeyCode>>, mut brush: ResMut<Brush>) {
+    if *mode != GameMode::Observer {
+        return;
+    }
+    if key.just_pressed(KeyCode::Key1) {
+        brush.0 = Species::Walnut;
+    }
+    if key.just_pressed(KeyCode::Key2) {
+        brush.0 = Species::Fox;
+    }
+    if key.just_pressed(KeyCode::Key3) {
+        brush.0 = Species::StrongBear;
+    }
+    if key.just_pressed(KeyCode::Key4) {
+        brush.0 = Species::WeakBear;
+    }
+}
+
+fn place_creature(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    brush: Res<Brush>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    camera_query: CameraQuery,
+    field_query: Query<&Position, With<Field>>,
+    asset_server: Res<AssetServer>,
+) {
+    if *mode != GameMode::Observer || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let grid = match cursor_grid(&windows, &camera_query) {
+        Some(grid) => grid,
+        None => return,
+    };
+    if !reachable(&field_query, grid.x, grid.y) {
+        return;
+    }
+
+    spawn_species(&mut commands, brush.0, grid, &asset_server);
+}
+
+fn brush_text(brush: Res<Brush>, mut text_query: Query<&mut Text, With<BrushText>>) {
+    let value = format!(
+        "Click: {}  (1:walnut 2:fox 3:strong_bear 4:weak_bear)",
+        brush.0.name()
+    );
+    text_query.iter_mut().for_each(|mut text| {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    });
+}
@@ -0,0 +1,134 @@
+use crate::components::*;
+use crate::consts::*;
+
+use bevy::prelude::*;
+
+/// Player が食べたものの記録
+#[derive(Default)]
+pub struct Record {
+    pub walnut: i32,
+    pub fox: i32,
+    pub weak_bear: i32,
+}
+
+impl Record {
+    /// 食べた数を数えている種類
+    pub fn tracks(species: Species) -> bool {
+        matches!(species, Species::Walnut | Species::Fox | Species::WeakBear)
+    }
+    pub fn count(&self, species: Species) -> i32 {
+        match species {
+            Species::Walnut => self.walnut,
+            Species::Fox => self.fox,
+            Species::WeakBear => self.weak_bear,
+            _ => 0,
+        }
+    }
+    pub fn add(&mut self, species: Species) {
+        match species {
+            Species::Walnut => self.walnut += 1,
+            Species::Fox => self.fox += 1,
+            Species::WeakBear => self.weak_bear += 1,
+            _ => {}
+        }
+    }
+}
+
+/// 出港時の島の生き物の数
+pub struct Population {
+    pub strong_bear: i32,
+    pub weak_bear: i32,
+    pub fox: i32,
+    pub walnut: i32,
+}
+
+/*
+ * Score
+ * 早く脱出するほど、HP が多く残っているほど高い。
+ * 食べた生き物に応じて加点し、出港時に生態系が崩れていたら減点する。
+ */
+pub struct Score {
+    pub escape_tick: i32,
+    pub time: i32,
+    pub hp: i32,
+    pub eaten: i32,
+    pub ecosystem: i32,
+}
+
+impl Score {
+    pub fn new(escape_tick: i32, hp: f32, record: &Record, population: &Population) -> Score {
+        let time = (SCORE_TIME_LIMIT - escape_tick).max(0) / GAME_DEFAULT_FPS * SCORE_PER_SEC;
+        let eaten = record.walnut * SCORE_WALNUT
+            + record.fox * SCORE_FOX
+            + record.weak_bear * SCORE_WEAK_BEAR;
+
+        let mut ecosystem = 0;
+        if population.strong_bear + population.weak_bear == 0 {
+            ecosystem += SCORE_BEAR_EXTINCT;
+        }
+        if population.fox == 0 {
+            ecosystem += SCORE_FOX_EXTINCT;
+        }
+        if population.walnut == 0 {
+            ecosystem += SCORE_WALNUT_EXTINCT;
+        }
+
+        Score {
+            escape_tick,
+            time,
+            hp: hp.max(0.0) as i32 * SCORE_PER_HP,
+            eaten,
+            ecosystem,
+        }
+    }
+    pub fn total(&self) -> i32 {
+        (self.time + self.hp + self.eaten + self.ecosystem).max(0)
+    }
+}
+
+#[derive(Component)]
+pub struct ResultText;
+
+pub fn spawn_result_text(
+    commands: &mut Commands,
+    score: &Score,
+    record: &Record,
+    asset_server: &Res<AssetServer>,
+) {
+    let value = format!(
+        "Escape: {}s  +{}\nHP: +{}\nWalnut x{} Fox x{} WeakBear x{}  +{}\nEcosystem: {}\nScore: {}",
+        score.escape_tick / GAME_DEFAULT_FPS,
+        score.time,
+        score.hp,
+        record.walnut,
+        record.fox,
+        record.weak_bear,
+        score.eaten,
+        score.ecosystem,
+        score.total(),
+    );
+
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px((2 * UNIT_WIDTH) as f32),
+                    bottom: Val::Px((12 * UNIT_HEIGHT) as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(ResultText);
+}
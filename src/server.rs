@@ -0,0 +1,342 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::net::*;
+use crate::simulation::*;
+use crate::title::Screen;
+use crate::{add_game, player_starts, spawn_player, GameClock, GameMode, Stage, StartGame};
+
+use bevy::app::ScheduleRunnerSettings;
+use bevy::asset::AssetPlugin;
+use bevy::input::InputPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/*
+ * 通信対戦のサーバー: cargo run -- --server [port]
+ * 画面を出さずに島を動かし、つないできたクライアントごとに Player を置く
+ * 島はずっと動き続け、船に乗った人や食べられた人は島からいなくなる
+ * 接続ごとにスレッドを立て、ゲームとは Connection の行でやりとりする
+ */
+pub fn port_from_args() -> Option<u16> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == "--server")?;
+    Some(
+        args.get(i + 1)
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(NET_DEFAULT_PORT),
+    )
+}
+
+pub fn run(port: u16) {
+    let mut app = App::new();
+    app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_millis(
+        NET_SERVER_FRAME_MS,
+    )))
+    .add_plugins(MinimalPlugins)
+    .add_plugin(LogPlugin)
+    .add_plugin(AssetPlugin)
+    .add_plugin(InputPlugin)
+    .insert_resource(Screen::Playing);
+    add_game(&mut app)
+        .insert_resource(GameMode::Server)
+        .insert_resource(Server::listen(port))
+        .add_startup_system(start)
+        .add_system_to_stage(CoreStage::PreUpdate, server_receive.after("actions"))
+        .add_system_set_to_stage(
+            SimulationStage,
+            tick_set(Tick::Sync).with_system(server_broadcast),
+        )
+        .run();
+}
+
+fn start(mut start_events: EventWriter<StartGame>) {
+    start_events.send(StartGame);
+}
+
+struct Client {
+    player: usize,
+    entity: Entity,
+    connection: Connection,
+    // まだ島の様子を丸ごと送っていない
+    fresh: bool,
+}
+
+/// 前の tick に送った生き物の様子
+#[derive(Clone, Copy, PartialEq)]
+struct Seen {
+    species: Species,
+    player: Option<usize>,
+    pos: Position,
+    hp: i32,
+}
+
+pub struct Server {
+    joined: Mutex<Receiver<Connection>>,
+    clients: Vec<Client>,
+    next_player: usize,
+    seen: HashMap<u64, Seen>,
+}
+
+impl Server {
+    fn listen(port: u16) -> Server {
+        match TcpListener::bind(("0.0.0.0", port)) {
+            Ok(listener) => {
+                info!("listening on ws://0.0.0.0:{}", port);
+                Server::accept(listener)
+            }
+            Err(e) => {
+                error!("failed to listen on {}: {}", port, e);
+                Server::new(channel().1)
+            }
+        }
+    }
+    fn accept(listener: TcpListener) -> Server {
+        let (join, joined) = channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let join = join.clone();
+                thread::spawn(move || {
+                    let mut socket = match tungstenite::accept(stream) {
+                        Ok(socket) => socket,
+                        Err(e) => {
+                            warn!("failed to accept: {}", e);
+                            return;
+                        }
+                    };
+                    if socket.get_mut().set_nonblocking(true).is_err() {
+                        return;
+                    }
+                    let connection = Connection::default();
+                    if join.send(connection.clone()).is_err() {
+                        return;
+                    }
+                    pump(&mut socket, &connection);
+                });
+            }
+        });
+        Server::new(joined)
+    }
+    fn new(joined: Receiver<Connection>) -> Server {
+        Server {
+            joined: Mutex::new(joined),
+            clients: vec![],
+            next_player: 0,
+            seen: HashMap::new(),
+        }
+    }
+}
+
+fn server_receive(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    stage: Res<Stage>,
+    mut server: ResMut<Server>,
+    mut remote_query: Query<&mut RemoteInput>,
+) {
+    let server = &mut *server;
+    // 来た人には島を送り、1P の近くから始めてもらう
+    let joined: Vec<Connection> = server.joined.lock().unwrap().try_iter().collect();
+    joined.into_iter().for_each(|connection| {
+        let player = server.next_player;
+        server.next_player += 1;
+        stage.level.to_text().lines().for_each(|line| {
+            connection.send(ServerMessage::Level(line.to_string()).to_line());
+        });
+        connection.send(ServerMessage::Welcome(player).to_line());
+
+        let start = *player_starts(&stage.level, player + 1)
+            .last()
+            .unwrap_or(&stage.level.player);
+        let position = Position {
+            z: PLAYER_LAYER,
+            ..start
+        };
+        let entity = spawn_player(
+            &mut commands,
+            position,
+            player,
+            Species::Human,
+            &asset_server,
+        );
+        commands.entity(entity).insert(RemoteInput::default());
+        info!("player {} joined", player);
+        server.clients.push(Client {
+            player,
+            entity,
+            connection,
+            fresh: true,
+        });
+    });
+
+    server.clients.iter().for_each(|client| {
+        while let Some(line) = client.connection.receive() {
+            match ClientMessage::parse(&line) {
+                Some(ClientMessage::Input(seq, direction)) => {
+                    if let Ok(mut remote) = remote_query.get_mut(client.entity) {
+                        remote.push(seq, direction);
+                    }
+                }
+                None => warn!("broken message from player {}: {}", client.player, line),
+            }
+        }
+    });
+
+    // 切れた人の Player は島から消す
+    server.clients.retain(|client| {
+        if !client.connection.is_closed() {
+            return true;
+        }
+        if remote_query.get(client.entity).is_ok() {
+            commands.entity(client.entity).despawn_recursive();
+        }
+        info!("player {} left", client.player);
+        false
+    });
+}
+
+fn spawn_message(id: u64, seen: &Seen) -> ServerMessage {
+    match seen.player {
+        Some(player) => ServerMessage::Player(id, player, seen.pos),
+        None => ServerMessage::Spawn(id, seen.species, seen.pos),
+    }
+}
+
+// tick ごとに、前の tick から変わったところだけを送る
+fn server_broadcast(
+    clock: Res<GameClock>,
+    mut server: ResMut<Server>,
+    creature_query: Query<(Entity, &Species, &Position, &HP, Option<&Player>)>,
+    remote_query: Query<&RemoteInput>,
+) {
+    let server = &mut *server;
+    // 食べられた生き物は tick の最後に消えるので、ここで消えたことにする
+    let now: HashMap<u64, Seen> = creature_query
+        .iter()
+        .filter(|(_, _, _, hp, _)| hp.val > 0.0)
+        .map(|(entity, species, pos, hp, player)| {
+            let seen = Seen {
+                species: *species,
+                player: player.map(|player| player.id),
+                pos: *pos,
+                hp: hp.val as i32,
+            };
+            (entity.to_bits(), seen)
+        })
+        .collect();
+
+    let mut diff = vec![ServerMessage::Tick(clock.tick)];
+    server.seen.keys().for_each(|id| {
+        if !now.contains_key(id) {
+            diff.push(ServerMessage::Despawn(*id));
+        }
+    });
+    now.iter().for_each(|(id, seen)| match server.seen.get(id) {
+        None => diff.push(spawn_message(*id, seen)),
+        Some(before) => {
+            if before.pos != seen.pos {
+                diff.push(ServerMessage::Move(*id, seen.pos));
+            }
+            if seen.player.is_some() && before.hp != seen.hp {
+                diff.push(ServerMessage::Hp(*id, seen.hp as f32));
+            }
+        }
+    });
+    let diff: Vec<String> = diff.iter().map(ServerMessage::to_line).collect();
+
+    server.clients.iter_mut().for_each(|client| {
+        if client.fresh {
+            // 来たばかりの人には島の様子を丸ごと送る
+            client.fresh = false;
+            client
+                .connection
+                .send(ServerMessage::Tick(clock.tick).to_line());
+            now.iter().for_each(|(id, seen)| {
+                client.connection.send(spawn_message(*id, seen).to_line());
+                if seen.player.is_some() {
+                    let hp = ServerMessage::Hp(*id, seen.hp as f32);
+                    client.connection.send(hp.to_line());
+                }
+            });
+        } else {
+            diff.iter()
+                .for_each(|line| client.connection.send(line.clone()));
+        }
+        if let Ok(remote) = remote_query.get(client.entity) {
+            client
+                .connection
+                .send(ServerMessage::Ack(remote.acked).to_line());
+        }
+    });
+    server.seen = now;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+    use bevy::asset::FileAssetIo;
+    // crate の Stage と名前がぶつかるので、Schedule::run を呼ぶためだけに入れる
+    use bevy::ecs::schedule::Stage as _;
+    use bevy::tasks::TaskPool;
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::Message;
+
+    // つないだクライアントに welcome と ack が届く
+    #[test]
+    fn client_gets_welcome_and_ack() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut world = World::new();
+        world.insert_resource(AssetServer::new(
+            FileAssetIo::new("assets", false),
+            TaskPool::new(),
+        ));
+        world.insert_resource(Stage {
+            level: Level::island(),
+            seed: 0,
+        });
+        world.insert_resource(GameClock { tick: 0 });
+        world.insert_resource(Server::accept(listener));
+        let mut schedule = Schedule::default();
+        schedule.add_stage(
+            "server",
+            SystemStage::parallel()
+                .with_system(server_receive.label("receive"))
+                .with_system(server_broadcast.after("receive")),
+        );
+
+        let (mut socket, _) = tungstenite::connect(format!("ws://127.0.0.1:{}", port)).unwrap();
+        if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+        }
+        // 接続がゲームに届くまで回し、Player を置いた次の回で ack を送る
+        for _ in 0..100 {
+            schedule.run(&mut world);
+            if !world.resource::<Server>().clients.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(NET_POLL_MS));
+        }
+        schedule.run(&mut world);
+
+        let mut lines = vec![];
+        while !lines.iter().any(|line| line == "ack 0") {
+            if let Message::Text(text) = socket.read_message().unwrap() {
+                lines.extend(text.lines().map(String::from));
+            }
+        }
+        assert!(lines.iter().any(|line| line == "welcome 0"));
+        let welcome = lines.iter().position(|line| line == "welcome 0").unwrap();
+        assert!(lines[..welcome]
+            .iter()
+            .all(|line| line.starts_with("level")));
+    }
+}
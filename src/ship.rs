@@ -0,0 +1,276 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::hud::{hud_bottom, hud_left};
+use crate::simulation::*;
+use crate::GameClock;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+pub struct ShipPlugin;
+
+impl Plugin for ShipPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Timetable::default())
+            .add_system_set_to_stage(SimulationStage, tick_set(Tick::Move).with_system(move_ship))
+            .add_system(ship_timer_text);
+    }
+}
+
+/// 航路: 寄港する Terminal と、そこを出て戻ってくるまでの移動量の列
+/// 船は interval tick ごとに Terminal に着き、docking tick の間だけ乗船できる
+#[derive(Clone)]
+pub struct ShipLine {
+    pub terminal: Position,
+    pub route: Vec<Position>,
+    pub first_arrival: i32,
+    pub interval: i32,
+    pub docking: i32,
+}
+
+impl ShipLine {
+    // 直前の到着からの経過 tick
+    fn phase(&self, tick: i32) -> i32 {
+        (tick - self.first_arrival).rem_euclid(self.interval)
+    }
+    pub fn is_docked(&self, tick: i32) -> bool {
+        tick >= self.first_arrival && self.phase(tick) < self.docking
+    }
+    pub fn ticks_to_arrival(&self, tick: i32) -> i32 {
+        if tick < self.first_arrival {
+            return self.first_arrival - tick;
+        }
+        self.interval - self.phase(tick)
+    }
+    pub fn ticks_to_departure(&self, tick: i32) -> i32 {
+        self.docking - self.phase(tick)
+    }
+    pub fn position(&self, tick: i32) -> Position {
+        if self.is_docked(tick) || self.route.is_empty() {
+            return self.terminal;
+        }
+        // 停泊していない時間で航路を一周する
+        let sailing = (self.interval - self.docking).max(1);
+        let elapsed = (self.phase(tick) - self.docking).rem_euclid(sailing);
+        let step = elapsed as usize * self.route.len() / sailing as usize;
+        self.route[..step]
+            .iter()
+            .fold(self.terminal, |pos, offset| &pos + offset)
+    }
+}
+
+/// 島に来る船の時刻表。ゲーム開始時に Level の航路から作る
+#[derive(Default)]
+pub struct Timetable {
+    pub lines: Vec<ShipLine>,
+}
+
+#[derive(Component)]
+pub struct ShipTimerText;
+
+pub fn spawn_harbor(
+    commands: &mut Commands,
+    timetable: &Timetable,
+    players: usize,
+    asset_server: &Res<AssetServer>,
+) {
+    let mut terminals: Vec<Position> = vec![];
+    for (line, ship_line) in timetable.lines.iter().enumerate() {
+        if !terminals.contains(&ship_line.terminal) {
+            terminals.push(ship_line.terminal);
+            spawn_terminal(
+                commands,
+                Position {
+                    z: TERMINAL_LAYER,
+                    ..ship_line.terminal
+                },
+            );
+        }
+        spawn_ship(
+            commands,
+            Position {
+                z: PLAYER_LAYER,
+                ..ship_line.position(0)
+            },
+            line,
+        );
+    }
+    spawn_ship_timer_text(commands, players, asset_server);
+}
+
+fn spawn_terminal(commands: &mut Commands, position: Position) {
+    let shape = shapes::Rectangle {
+        extents: Vec2::new(UNIT_WIDTH as f32, UNIT_HEIGHT as f32),
+        ..shapes::Rectangle::default()
+    };
+
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(Color::rgb(0.7, 0.7, 1.0)),
+                outline_mode: StrokeMode::new(Color::BLACK, 0.0),
+            },
+            Transform::default(),
+        ))
+        .insert(position)
+        .insert(Terminal);
+}
+
+fn spawn_ship(commands: &mut Commands, position: Position, line: usize) {
+    let shape = shapes::Rectangle {
+        extents: Vec2::new(UNIT_WIDTH as f32, UNIT_HEIGHT as f32),
+        ..shapes::Rectangle::default()
+    };
+
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Outlined {
+                fill_mode: FillMode::color(Color::WHITE),
+                outline_mode: StrokeMode::new(Color::BLACK, 0.0),
+            },
+            Transform::default(),
+        ))
+        .insert(position)
+        .insert(Ship {
+            line,
+            docked: false,
+        });
+}
+
+fn spawn_ship_timer_text(commands: &mut Commands, players: usize, asset_server: &Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                // HUD の下に並べる
+                position: Rect {
+                    left: Val::Px(hud_left()),
+                    top: Val::Px(hud_bottom(players)),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(ShipTimerText);
+}
+
+pub fn move_ship(
+    clock: Res<GameClock>,
+    timetable: Res<Timetable>,
+    mut ship_query: Query<(&mut Ship, &mut Position)>,
+) {
+    ship_query.iter_mut().for_each(|(mut ship, mut pos)| {
+        let line = &timetable.lines[ship.line];
+        let decided_pos = line.position(clock.tick);
+        pos.x = decided_pos.x;
+        pos.y = decided_pos.y;
+        ship.docked = line.is_docked(clock.tick);
+    })
+}
+
+fn ship_timer_text(
+    clock: Res<GameClock>,
+    timetable: Res<Timetable>,
+    mut text_query: Query<&mut Text, With<ShipTimerText>>,
+) {
+    let value = timetable
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if line.is_docked(clock.tick) {
+                format!(
+                    "Ship{}: board! {}s",
+                    i + 1,
+                    line.ticks_to_departure(clock.tick) / GAME_DEFAULT_FPS
+                )
+            } else {
+                format!(
+                    "Ship{}: {}s",
+                    i + 1,
+                    line.ticks_to_arrival(clock.tick) / GAME_DEFAULT_FPS
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    text_query.iter_mut().for_each(|mut text| {
+        text.sections[0].value = value.clone();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::grid;
+
+    // 100 tick に初めて着き、50 tick ごとに 10 tick 停泊して、残りの 40 tick で 6 マスを回る
+    fn line() -> ShipLine {
+        ShipLine {
+            terminal: grid(0, 0),
+            route: vec![
+                Position::right(),
+                Position::right(),
+                Position::up(),
+                Position::left(),
+                Position::left(),
+                Position::down(),
+            ],
+            first_arrival: 100,
+            interval: 50,
+            docking: 10,
+        }
+    }
+
+    #[test]
+    fn first_arrival() {
+        let line = line();
+        assert!(!line.is_docked(0));
+        assert!(!line.is_docked(99));
+        assert!(line.is_docked(100));
+        assert_eq!(line.ticks_to_arrival(0), 100);
+        assert_eq!(line.ticks_to_arrival(99), 1);
+        assert_eq!(line.ticks_to_arrival(100), 50);
+        assert_eq!(line.ticks_to_departure(100), 10);
+        assert_eq!(line.position(100), grid(0, 0));
+    }
+
+    #[test]
+    fn end_of_docking() {
+        let line = line();
+        assert!(line.is_docked(109));
+        assert_eq!(line.ticks_to_departure(109), 1);
+        assert!(!line.is_docked(110));
+        assert_eq!(line.ticks_to_arrival(110), 40);
+        // 出たばかりの船はまだ Terminal にいて、少しずつ航路を進む
+        assert_eq!(line.position(110), grid(0, 0));
+        assert_eq!(line.position(117), grid(1, 0));
+        assert_eq!(line.position(130), grid(2, 1));
+    }
+
+    #[test]
+    fn wraps_around_to_the_next_interval() {
+        let line = line();
+        assert!(!line.is_docked(149));
+        assert_eq!(line.ticks_to_arrival(149), 1);
+        assert_eq!(line.position(149), grid(0, 1));
+        assert!(line.is_docked(150));
+        assert_eq!(line.ticks_to_arrival(150), 50);
+        assert_eq!(line.position(150), grid(0, 0));
+        assert!(line.is_docked(1059));
+        assert!(!line.is_docked(1060));
+    }
+}
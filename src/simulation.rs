@@ -0,0 +1,299 @@
+use crate::consts::*;
+use crate::input::*;
+use crate::GameClock;
+
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
+pub struct SimulationPlugin;
+
+/// ゲームの進行はすべてこの Stage で、1 秒に GAME_FPS 回だけ動かす
+/// 描画が遅れたフレームでは、溜まった分をまとめて追いかける
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct SimulationStage;
+
+/*
+ * 1 tick の中の順番
+ * Input    : tick を進めて入力を受け取る
+ * Age      : 歳をとる、お腹が減る、Stamina が回復する
+ * Eat      : 同じマスにいる獲物を食べる
+ * Eaten    : 食べられた獲物の HP を 0 にする
+ * Death    : HP が 0 になったものを despawn する
+ * Reproduce: 満腹なら増える
+ * Move     : 動く
+ * Goal     : 船に乗れたか調べる
+ * Sync     : 描画に動いた結果を伝える
+ *
+ * despawn は tick の最後に反映されるので、Death より後の system は
+ * HP が 0 のものを無視すること (食べられた獲物が増えたり動いたりしないように)
+ */
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, SystemLabel)]
+pub enum Tick {
+    Input,
+    Age,
+    Eat,
+    Eaten,
+    Death,
+    Reproduce,
+    Move,
+    Goal,
+    Sync,
+}
+
+impl Tick {
+    pub const ORDER: [Tick; 9] = [
+        Tick::Input,
+        Tick::Age,
+        Tick::Eat,
+        Tick::Eaten,
+        Tick::Death,
+        Tick::Reproduce,
+        Tick::Move,
+        Tick::Goal,
+        Tick::Sync,
+    ];
+    fn previous(self) -> Option<Tick> {
+        let i = Tick::ORDER.iter().position(|tick| *tick == self)?;
+        i.checked_sub(1).map(|i| Tick::ORDER[i])
+    }
+}
+
+/// Tick の順番を守る SystemSet。SimulationStage の system は必ずこれで追加する
+pub fn tick_set(tick: Tick) -> SystemSet {
+    let set = SystemSet::new().label(tick);
+    match tick.previous() {
+        Some(previous) => set.after(previous),
+        None => set,
+    }
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Simulation::default())
+            .add_stage_after(
+                CoreStage::Update,
+                SimulationStage,
+                SystemStage::parallel().with_run_criteria(simulation_step),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                accumulate.after("simulation_controls"),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                simulation_controls
+                    .label("simulation_controls")
+                    .after("actions"),
+            )
+            .add_system(simulation_indicator)
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Input).with_system(advance_clock),
+            );
+    }
+}
+
+pub struct Simulation {
+    accumulator: f64,
+    pending: u32,
+    speed: usize,
+    pub paused: bool,
+    // 止めている間に 1 tick だけ進める
+    step_requested: bool,
+    /// 通信対戦ではサーバーが島を動かすので、ここでは進めない
+    pub remote: bool,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation {
+            accumulator: 0.0,
+            pending: 0,
+            speed: GAME_DEFAULT_SPEED,
+            paused: false,
+            step_requested: false,
+            remote: false,
+        }
+    }
+}
+
+impl Simulation {
+    fn step() -> f64 {
+        GAME_TICK as f64 / 1000.0
+    }
+    pub fn speed(&self) -> f64 {
+        GAME_SPEEDS[self.speed]
+    }
+    pub fn speed_up(&mut self) {
+        self.speed = usize::min(self.speed + 1, GAME_SPEEDS.len() - 1);
+    }
+    pub fn slow_down(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+    /// 次の tick までの進み具合 (0.0 ~ 1.0)
+    pub fn progress(&self) -> f32 {
+        (self.accumulator / Simulation::step()) as f32
+    }
+    pub fn request_step(&mut self) {
+        self.step_requested = true;
+    }
+}
+
+fn accumulate(time: Res<Time>, mut sim: ResMut<Simulation>) {
+    if sim.remote {
+        sim.pending = 0;
+        return;
+    }
+    if sim.paused {
+        sim.pending = if sim.step_requested { 1 } else { 0 };
+        sim.step_requested = false;
+        return;
+    }
+    sim.accumulator += time.delta_seconds_f64() * sim.speed();
+    let steps = (sim.accumulator / Simulation::step()) as u32;
+    sim.accumulator -= steps as f64 * Simulation::step();
+    // 追いつけないほど遅れたら、古い分は捨てる
+    sim.pending = u32::min(steps, MAX_CATCH_UP_TICKS);
+}
+
+fn simulation_step(mut sim: ResMut<Simulation>) -> ShouldRun {
+    if sim.pending == 0 {
+        return ShouldRun::No;
+    }
+    sim.pending -= 1;
+    ShouldRun::YesAndCheckAgain
+}
+
+fn advance_clock(mut clock: ResMut<GameClock>) {
+    clock.tick += 1;
+}
+
+// Pause: 一時停止 / 再開, Step: 止めている間に 1 tick 進める, SpeedUp / SlowDown: 速さを変える
+fn simulation_controls(actions: Res<Actions>, mut sim: ResMut<Simulation>) {
+    if actions.just_pressed(Action::Pause) {
+        sim.paused = !sim.paused;
+    }
+    if actions.just_pressed(Action::Step) && sim.paused {
+        sim.request_step();
+    }
+    if actions.just_pressed(Action::SpeedUp) {
+        sim.speed_up();
+    }
+    if actions.just_pressed(Action::SlowDown) {
+        sim.slow_down();
+    }
+}
+
+#[derive(Component)]
+pub struct SimulationText;
+
+pub fn spawn_simulation_indicator(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(UNIT_WIDTH as f32),
+                    top: Val::Px(UNIT_HEIGHT as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(SimulationText);
+}
+
+fn simulation_indicator(
+    sim: Res<Simulation>,
+    mut text_query: Query<&mut Text, With<SimulationText>>,
+) {
+    let value = if sim.paused {
+        "PAUSED (N: step)".to_string()
+    } else if sim.speed != GAME_DEFAULT_SPEED {
+        format!("x{}", sim.speed())
+    } else {
+        String::new()
+    };
+    text_query.iter_mut().for_each(|mut text| {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::*;
+    use crate::eat::eaten_fox;
+    use crate::{increase_fox, move_fox, Stamina};
+    use bevy::asset::FileAssetIo;
+    use bevy::tasks::TaskPool;
+
+    // 食べられた Fox は、同じ tick に増えたり動いたりしない
+    #[test]
+    fn eaten_prey_neither_breeds_nor_moves() {
+        let mut world = World::new();
+        world.insert_resource(AssetServer::new(
+            FileAssetIo::new("assets", false),
+            TaskPool::new(),
+        ));
+        for x in -1..=1 {
+            for y in -1..=1 {
+                world.spawn().insert(Position { x, y, z: 0 }).insert(Field);
+            }
+        }
+        let cell = Position { x: 0, y: 0, z: 0 };
+        // Fox が追いかける Walnut。生きていれば隣へ動こうとする
+        world
+            .spawn()
+            .insert(Position { x: 1, y: 0, z: 0 })
+            .insert(FoxPrey);
+        world
+            .spawn()
+            .insert(cell)
+            .insert(StrongBear)
+            .insert(FoxEater)
+            .insert(HP::bear(MAX_HP_BEAR));
+        let mut satiety = Satiety::fox();
+        satiety.val = satiety.max;
+        let mut stamina = Stamina::fox();
+        stamina.val = MAX_STAMINA;
+        let fox = world
+            .spawn()
+            .insert(cell)
+            .insert(Fox)
+            .insert(HP::fox())
+            .insert(satiety)
+            .insert(stamina)
+            .id();
+
+        // Death の無い Stage では tick_set の順番がつながらないので、直接並べる
+        let mut stage = SystemStage::parallel();
+        stage
+            .add_system(eaten_fox.label(Tick::Eaten))
+            .add_system(increase_fox.label(Tick::Reproduce).after(Tick::Eaten))
+            .add_system(move_fox.after(Tick::Reproduce));
+        let mut schedule = Schedule::default();
+        schedule.add_stage(SimulationStage, stage);
+        schedule.run(&mut world);
+
+        assert!(world.get::<HP>(fox).unwrap().val <= 0.0);
+        assert_eq!(*world.get::<Position>(fox).unwrap(), cell);
+        let foxes = world
+            .query_filtered::<Entity, With<Fox>>()
+            .iter(&world)
+            .count();
+        assert_eq!(foxes, 1);
+    }
+}
@@ -0,0 +1,46 @@
+/*
+ * 小さなテキストの保存先
+ * native: カレントディレクトリのファイル
+ * wasm  : ブラウザの localStorage
+ */
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(name: &str) -> Option<String> {
+    std::fs::read_to_string(name).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(name: &str, value: &str) {
+    if let Err(e) = std::fs::write(name, value) {
+        bevy::log::warn!("failed to save {}: {}", name, e);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+// ハイスコアは STORAGE_PREFIX を付ける前からのキーのまま
+#[cfg(target_arch = "wasm32")]
+fn key(name: &str) -> String {
+    use crate::consts::*;
+    if name == HIGHSCORE_FILE {
+        return HIGHSCORE_STORAGE_KEY.to_string();
+    }
+    format!("{}{}", STORAGE_PREFIX, name)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(name: &str) -> Option<String> {
+    local_storage()?.get_item(&key(name)).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(name: &str, value: &str) {
+    if let Some(storage) = local_storage() {
+        if storage.set_item(&key(name), value).is_err() {
+            bevy::log::warn!("failed to save {}", name);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::character::*;
+use crate::consts::*;
+use crate::difficulty::Difficulty;
+use crate::generator::generate;
+use crate::highscore::*;
+use crate::input::*;
+use crate::level::Level;
+use crate::net::{connect, server_url};
+use crate::storage;
+use crate::{GameMode, Stage, StartGame};
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+pub struct TitlePlugin;
+
+impl Plugin for TitlePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Screen::Title)
+            // イニシャルを確定した Enter でゲームが始まらないように
+            .add_system(title_input.before("name_entry"))
+            .add_system(title_text);
+    }
+}
+
+/// いま表示している画面
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Screen {
+    Title,
+    Playing,
+    NameEntry,
+    Editor,
+    LevelSelect,
+}
+
+#[derive(Component)]
+pub struct TitleText;
+
+pub fn spawn_title(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px((2 * UNIT_WIDTH) as f32),
+                    top: Val::Px((2 * UNIT_HEIGHT) as f32),
+                    ..default()
+                },
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(TitleText);
+}
+
+fn title_input(
+    mut commands: Commands,
+    screen: Res<Screen>,
+    mut stage: ResMut<Stage>,
+    mut mode: ResMut<GameMode>,
+    mut character: ResMut<Character>,
+    mut difficulty: ResMut<Difficulty>,
+    key: Res<Input<KeyCode>>,
+    actions: Res<Actions>,
+    mut start_events: EventWriter<StartGame>,
+) {
+    if *screen != Screen::Title {
+        return;
+    }
+    if actions.just_pressed(Action::Interact) {
+        *mode = GameMode::Normal;
+        start_events.send(StartGame);
+    }
+    if key.just_pressed(KeyCode::O) {
+        *mode = GameMode::Observer;
+        start_events.send(StartGame);
+    }
+    if key.just_pressed(KeyCode::Key2) {
+        *mode = GameMode::Coop;
+        start_events.send(StartGame);
+    }
+    if key.just_pressed(KeyCode::V) {
+        *mode = GameMode::Versus;
+        start_events.send(StartGame);
+    }
+    // サーバーから島が届いたら始まる
+    if key.just_pressed(KeyCode::J) {
+        commands.insert_resource(connect(&server_url()));
+    }
+    if key.just_pressed(KeyCode::D) {
+        *difficulty = difficulty.next();
+    }
+    if key.just_pressed(KeyCode::H) {
+        *character = character.next();
+    }
+    if key.just_pressed(KeyCode::S) {
+        stage.seed = thread_rng().gen_range(0..10000);
+    }
+    // いつもの島 -> seed から作る島 -> エディタで保存した島 の順に切り替える
+    if key.just_pressed(KeyCode::C) {
+        stage.level = match stage.level.name.as_str() {
+            DEFAULT_LEVEL => generate(stage.seed),
+            PROCEDURAL_LEVEL => load_custom_level().unwrap_or_else(Level::island),
+            _ => Level::island(),
+        };
+    }
+    if key.just_pressed(KeyCode::S) && stage.level.name == PROCEDURAL_LEVEL {
+        stage.level = generate(stage.seed);
+    }
+}
+
+fn load_custom_level() -> Option<Level> {
+    match Level::parse(&storage::read(CUSTOM_LEVEL_FILE)?) {
+        Ok(level) if level.validate().is_ok() => Some(level),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("failed to load {}: {}", CUSTOM_LEVEL_FILE, e);
+            None
+        }
+    }
+}
+
+fn title_text(
+    screen: Res<Screen>,
+    stage: Res<Stage>,
+    character: Res<Character>,
+    difficulty: Res<Difficulty>,
+    leaderboard: Res<Leaderboard>,
+    mut text_query: Query<&mut Text, With<TitleText>>,
+) {
+    if *screen != Screen::Title {
+        return;
+    }
+    let mut value = format!(
+        "FoodChainGame\n\nLevel: {} (C: change)\nSeed: {} (S: change)\nDifficulty: {} (D: change)\nCharacter: {} - {} (H: change)\nPress Enter to start\nL: campaign\n2: co-op  V: versus (2P: IJKL)\nJ: join server\nO: observer mode\nE: level editor\n\nHigh Scores\n",
+        stage.level.name,
+        stage.seed,
+        difficulty.name(),
+        character.0.name(),
+        default_objective(character.0).describe()
+    );
+    leaderboard
+        .top(&stage.level.name, stage.seed, *difficulty)
+        .iter()
+        .enumerate()
+        .for_each(|(i, entry)| {
+            value.push_str(&format!(
+                "{:>2}. {:<3} {}\n",
+                i + 1,
+                entry.initials,
+                entry.score
+            ));
+        });
+
+    text_query.iter_mut().for_each(|mut text| {
+        text.sections[0].value = value.clone();
+    });
+}
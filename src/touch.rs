@@ -0,0 +1,223 @@
+use crate::camera::{window_grid, CameraQuery};
+use crate::components::*;
+use crate::consts::*;
+use crate::input::*;
+use crate::title::Screen;
+use crate::GameMode;
+
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub struct TouchPlugin;
+
+/*
+ * スマホ (wasm) でも遊べるように
+ * タップ / クリックしたマスへ Player が歩いていく。キーで動かしたらやめる
+ * 画面左下の十字キーは移動の Action を押したことにする
+ * wasm ではブラウザの大きさに合わせて画面を拡大縮小する
+ */
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(tap_to_move).add_system(dpad);
+        #[cfg(target_arch = "wasm32")]
+        app.add_system(fit_canvas);
+    }
+}
+
+/// Player が歩いていく先
+#[derive(Component)]
+pub struct MoveTarget(pub Position);
+
+#[derive(Component)]
+pub struct DpadButton(Action);
+
+/// from から to へ島の上を通って行くときの最初の 1 歩
+pub fn next_step(
+    fields: &HashSet<(i32, i32)>,
+    from: &Position,
+    to: &Position,
+) -> Option<(i32, i32)> {
+    let start = (from.x, from.y);
+    let goal = (to.x, to.y);
+    if start == goal || !fields.contains(&goal) {
+        return None;
+    }
+    // goal から幅優先で広げ、start の隣で一番 goal に近いマスへ進む
+    let mut distance = HashMap::new();
+    let mut queue = VecDeque::new();
+    distance.insert(goal, 0);
+    queue.push_back(goal);
+    while let Some((x, y)) = queue.pop_front() {
+        let d = distance[&(x, y)];
+        if (x, y) == start {
+            break;
+        }
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = (x + dx, y + dy);
+                if (next == start || fields.contains(&next)) && !distance.contains_key(&next) {
+                    distance.insert(next, d + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    let (x, y) = start;
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|&step| step != (0, 0))
+        .filter_map(|(dx, dy)| distance.get(&(x + dx, y + dy)).map(|d| (*d, (dx, dy))))
+        .min()
+        .map(|(_, step)| step)
+}
+
+fn tap_to_move(
+    mut commands: Commands,
+    screen: Res<Screen>,
+    mode: Res<GameMode>,
+    windows: Res<Windows>,
+    touches: Res<Touches>,
+    mouse: Res<Input<MouseButton>>,
+    camera_query: CameraQuery,
+    dpad_query: Query<&Interaction, With<DpadButton>>,
+    field_query: Query<&Position, With<Field>>,
+    player_query: Query<(Entity, &Player)>,
+) {
+    // 通信対戦の Player はサーバーが動かす
+    if *screen != Screen::Playing || mode.players() == 0 || *mode == GameMode::Online {
+        return;
+    }
+    // 十字キーを押したときは歩かせない
+    if dpad_query.iter().any(|i| *i != Interaction::None) {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    // タッチの位置は左上が原点
+    let tapped = touches
+        .iter_just_pressed()
+        .map(|touch| Vec2::new(touch.position().x, window.height() - touch.position().y))
+        .last()
+        .or_else(|| {
+            if mouse.just_pressed(MouseButton::Left) {
+                window.cursor_position()
+            } else {
+                None
+            }
+        });
+    let grid = match tapped.and_then(|pos| window_grid(&windows, &camera_query, pos)) {
+        Some(grid) => grid,
+        None => return,
+    };
+    if !field_query.iter().any(|pos| *pos == grid) {
+        return;
+    }
+    // タップと十字キーで動かせるのは 1P だけ
+    player_query
+        .iter()
+        .filter(|(_, player)| player.id == 0)
+        .for_each(|(entity, _)| {
+            commands.entity(entity).insert(MoveTarget(grid));
+        });
+}
+
+pub fn spawn_dpad(commands: &mut Commands, asset_server: &Res<AssetServer>) {
+    let buttons = [
+        (Action::MoveUpLeft, 0, 2, "\\"),
+        (Action::MoveUp, 1, 2, "^"),
+        (Action::MoveUpRight, 2, 2, "/"),
+        (Action::MoveLeft, 0, 1, "<"),
+        (Action::MoveRight, 2, 1, ">"),
+        (Action::MoveDownLeft, 0, 0, "/"),
+        (Action::MoveDown, 1, 0, "v"),
+        (Action::MoveDownRight, 2, 0, "\\"),
+    ];
+    buttons.iter().for_each(|(action, col, row, label)| {
+        commands
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(DPAD_BUTTON_SIZE), Val::Px(DPAD_BUTTON_SIZE)),
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Px(UNIT_WIDTH as f32 + *col as f32 * DPAD_BUTTON_SIZE),
+                        bottom: Val::Px(*row as f32 * DPAD_BUTTON_SIZE),
+                        ..default()
+                    },
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                color: UiColor(DPAD_COLOR),
+                ..Default::default()
+            })
+            .insert(DpadButton(*action))
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        *label,
+                        TextStyle {
+                            font_size: HUD_FONT_SIZE * 1.5,
+                            color: Color::WHITE,
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+    });
+}
+
+// 押している間は移動の Action を押したことにする。押した瞬間は先行入力にも入れる
+fn dpad(
+    bindings: Res<Bindings>,
+    mut actions: ResMut<Actions>,
+    mut button_query: Query<(
+        &DpadButton,
+        &Interaction,
+        ChangeTrackers<Interaction>,
+        &mut UiColor,
+    )>,
+) {
+    button_query
+        .iter_mut()
+        .for_each(|(button, interaction, tracker, mut color)| {
+            if *interaction == Interaction::Clicked {
+                if tracker.is_changed() {
+                    actions.tap(button.0, bindings.buffer);
+                } else {
+                    actions.press(button.0);
+                }
+                color.0 = DPAD_PRESSED_COLOR;
+            } else {
+                color.0 = DPAD_COLOR;
+            }
+        });
+}
+
+// 画面の見た目の大きさだけを変え、ゲームの座標は SCREEN_WIDTH * UNIT_WIDTH のままにする
+#[cfg(target_arch = "wasm32")]
+fn fit_canvas(mut windows: ResMut<Windows>) {
+    let browser = match web_sys::window() {
+        Some(browser) => browser,
+        None => return,
+    };
+    let width = browser.inner_width().ok().and_then(|v| v.as_f64());
+    let height = browser.inner_height().ok().and_then(|v| v.as_f64());
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return,
+    };
+    let base_width = (SCREEN_WIDTH * UNIT_WIDTH) as f64;
+    let base_height = (SCREEN_HEIGHT * UNIT_HEIGHT) as f64;
+    let scale = f64::min(width / base_width, height / base_height);
+
+    if let Some(window) = windows.get_primary_mut() {
+        if (window.scale_factor() - scale).abs() > 0.01 {
+            window.set_scale_factor_override(Some(scale));
+            window.set_resolution(base_width as f32, base_height as f32);
+        }
+    }
+}
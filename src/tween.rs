@@ -0,0 +1,96 @@
+use crate::components::*;
+use crate::consts::*;
+use crate::simulation::*;
+use crate::{get_render_position, GameClock, Stamina};
+
+use bevy::prelude::*;
+
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(attach_tween)
+            .add_system_set_to_stage(
+                SimulationStage,
+                tick_set(Tick::Sync).with_system(update_tween),
+            )
+            .add_system(tween_transform);
+    }
+}
+
+/// 1 マス前の Position から今の Position まで、次に動けるまでの時間をかけて描画を動かす
+#[derive(Component)]
+pub struct Tween {
+    from: Position,
+    to: Position,
+    moved_tick: i32,
+    ticks: i32,
+}
+
+// Stamina が溜まって次に動けるまでの tick 数
+fn ticks_per_move(stamina: &Stamina) -> i32 {
+    if stamina.healing_val <= 0 {
+        return 1;
+    }
+    (MAX_STAMINA + stamina.healing_val - 1) / stamina.healing_val
+}
+
+fn attach_tween(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    mover_query: Query<(Entity, &Position), (Added<Position>, Or<(With<Stamina>, With<Ship>)>)>,
+) {
+    mover_query.iter().for_each(|(entity, pos)| {
+        commands.entity(entity).insert(Tween {
+            from: *pos,
+            to: *pos,
+            moved_tick: clock.tick,
+            ticks: 1,
+        });
+    });
+}
+
+pub fn update_tween(
+    clock: Res<GameClock>,
+    mut mover_query: Query<(&Position, &mut Tween, Option<&Stamina>)>,
+) {
+    mover_query
+        .iter_mut()
+        .for_each(|(pos, mut tween, stamina)| {
+            if *pos == tween.to {
+                return;
+            }
+            // 船の航路の切り替えなど、1 マスより遠くへ飛んだときは補間しない
+            let jumped = (pos.x - tween.to.x).abs() > 1 || (pos.y - tween.to.y).abs() > 1;
+            tween.from = if jumped { *pos } else { tween.to };
+            tween.to = *pos;
+            // Stamina が無いもの (船) は前回動いてからの間隔を使う
+            tween.ticks = stamina
+                .map(ticks_per_move)
+                .unwrap_or(clock.tick - tween.moved_tick)
+                .max(1);
+            tween.moved_tick = clock.tick;
+        });
+}
+
+fn tween_transform(
+    clock: Res<GameClock>,
+    sim: Res<Simulation>,
+    mut mover_query: Query<(&Position, &Tween, &mut Transform)>,
+) {
+    mover_query
+        .iter_mut()
+        .for_each(|(pos, tween, mut transform)| {
+            // tick の途中も Simulation の進み具合で滑らかに動かす
+            let elapsed = (clock.tick - tween.moved_tick) as f32 + sim.progress();
+            let t = (elapsed / tween.ticks as f32).clamp(0.0, 1.0);
+
+            let from = get_render_position(&tween.from);
+            let to = get_render_position(&tween.to);
+            transform.translation = Vec3::new(
+                from.x as f32 + (to.x - from.x) as f32 * t,
+                from.y as f32 + (to.y - from.y) as f32 * t,
+                pos.z as f32,
+            );
+        });
+}